@@ -0,0 +1,15 @@
+use bevy::prelude::*;
+
+#[derive(Component)]
+pub struct Player {
+    pub speed: f32,
+}
+
+impl From<f32> for Player {
+    fn from(speed: f32) -> Self {
+        Self { speed }
+    }
+}
+
+#[derive(Resource)]
+pub struct Money(pub f32);
@@ -4,20 +4,14 @@ use pig::PigPlugin;
 use prefab::DefaultPrefabsPlugin;
 use ui::GameUiPlugin;
 
-use hana_prefab::room::{Room, RoomPlugin};
+use components::{Money, Player};
+use hana_prefab::room::{PrefabSpawned, Room, RoomLoaderSettings, RoomPlugin, RoomWriter};
 
+mod components;
 mod pig;
 mod prefab;
 mod ui;
 
-#[derive(Component)]
-struct Player {
-    speed: f32,
-}
-
-#[derive(Resource)]
-struct Money(f32);
-
 fn main() {
     App::new()
         .add_plugins((
@@ -42,8 +36,10 @@ fn main() {
             DefaultPrefabsPlugin,
         ))
         .add_systems(Startup, setup)
-        .add_systems(Update, (movement_system, remove_rooms))
-        .insert_resource(Money(100.0))
+        .add_systems(
+            Update,
+            (movement_system, follow_player, remove_rooms, save_rooms),
+        )
         .run();
 }
 
@@ -58,8 +54,12 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
 
     commands.spawn(camera);
 
-    let room: Handle<Room> = asset_server.load("rooms/test_room.ron");
-    commands.spawn((Name::new("main_room"), room));
+    // Spawn the room once the sprites it refers to are loaded, so no prefab pops in without its sprite
+    let room: Handle<Room> = asset_server.load_with_settings(
+        "rooms/test_room.ron",
+        |settings: &mut RoomLoaderSettings| settings.wait_for_dependencies = true,
+    );
+    commands.spawn((Name::new("main_room"), room, SpatialBundle::default()));
 }
 
 fn movement_system(
@@ -83,6 +83,29 @@ fn movement_system(
     }
 }
 
+/// Keeps the camera on the player spawned by the room
+fn follow_player(
+    mut spawned: EventReader<PrefabSpawned>,
+    mut player: Local<Option<Entity>>,
+    transforms: Query<&GlobalTransform>,
+    mut cameras: Query<&mut Transform, With<Camera>>,
+) {
+    for event in spawned.read() {
+        if event.prefab_type == "Player" {
+            *player = Some(event.entity);
+        }
+    }
+
+    let Some(player_transform) = player.and_then(|player| transforms.get(player).ok()) else {
+        return;
+    };
+
+    for mut camera_transform in &mut cameras {
+        camera_transform.translation.x = player_transform.translation().x;
+        camera_transform.translation.y = player_transform.translation().y;
+    }
+}
+
 fn remove_rooms(
     rooms: Query<Entity, With<Handle<Room>>>,
     mut commands: Commands,
@@ -90,12 +113,27 @@ fn remove_rooms(
 ) {
     if keyboard_input.just_pressed(KeyCode::KeyQ) {
         for entity in &rooms {
-            if let Some(mut commands) = commands.get_entity(entity) {
+            if let Some(commands) = commands.get_entity(entity) {
                 info!("Despawned room entity{entity:?}");
-                commands.despawn();
+                commands.despawn_recursive();
             } else {
                 warn!("Tried to get entity that did not exist")
             }
         }
     }
 }
+
+fn save_rooms(
+    rooms: Query<Entity, With<Handle<Room>>>,
+    writer: RoomWriter,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        for room in &rooms {
+            match writer.write_room(room, "assets/rooms/saved_room.ron") {
+                Ok(()) => info!("Saved room to assets/rooms/saved_room.ron"),
+                Err(error) => warn!("{error}"),
+            }
+        }
+    }
+}
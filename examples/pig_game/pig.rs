@@ -6,7 +6,10 @@ pub struct PigPlugin;
 
 impl Plugin for PigPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (spawn_pig_system, update_pig_system));
+        app.add_systems(
+            Update,
+            (spawn_pig_system, update_pig_system).run_if(resource_exists::<Money>),
+        );
     }
 }
 
@@ -19,11 +22,20 @@ struct Pig {
 #[derive(Component)]
 pub struct PigParent;
 
+/// The entity the pigs bought by a player are parented to
+#[derive(Component)]
+pub struct PigPen(pub Entity);
+
+impl From<Entity> for PigPen {
+    fn from(entity: Entity) -> Self {
+        Self(entity)
+    }
+}
+
 fn spawn_pig_system(
     mut commands: Commands,
     mut money: ResMut<Money>,
-    player: Query<&Transform, With<Player>>,
-    pig_parent: Query<Entity, With<PigParent>>,
+    player: Query<(&Transform, &PigPen), With<Player>>,
     asset_server: Res<AssetServer>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
 ) {
@@ -34,10 +46,9 @@ fn spawn_pig_system(
     money.0 -= 10.0;
 
     let texture = asset_server.load("sprites/Animals/pig.png");
-    let player_transform = player.single();
-    let pig_parent = pig_parent.single();
+    let (player_transform, pig_pen) = player.single();
 
-    commands.entity(pig_parent).with_children(|commands| {
+    commands.entity(pig_pen.0).with_children(|commands| {
         commands.spawn((
             SpriteBundle {
                 texture,
@@ -1,6 +1,13 @@
-use bevy::{ecs::system::EntityCommands, prelude::*, utils::HashMap};
+use bevy::{
+    ecs::{system::EntityCommands, world::EntityRef},
+    prelude::*,
+    utils::HashMap,
+};
 
-use hana_prefab::room::{Prefab, PrefabField, PrefabRegistry};
+use hana_prefab::room::{
+    Prefab, PrefabChangeSet, PrefabField, PrefabFields, PrefabRegistry, PrefabSchema,
+    ResourcePrefab,
+};
 
 pub struct DefaultPrefabsPlugin;
 
@@ -13,91 +20,52 @@ impl Plugin for DefaultPrefabsPlugin {
 fn setup(mut registry: ResMut<PrefabRegistry>) {
     trace!("prefab plugin setup");
 
-    registry.register_prefab("Player", PlayerPrefab);
+    registry.register_derived::<PlayerPrefab>("Player");
     registry.register_prefab("PigParent", PigParentPrefab);
+    registry.register_resource("Money", MoneyResource);
 }
 
-pub struct PlayerPrefab;
+/// The player, its fields are read from the room by the derive
+#[derive(Prefab)]
+#[prefab(bundle = "player_bundle", extract = "extract_player")]
+pub struct PlayerPrefab {
+    #[prefab(asset)]
+    sprite: Handle<Image>,
+    #[prefab(with = "position_transform")]
+    position: Vec2,
+    #[prefab(component = crate::Player, default = "default_speed")]
+    speed: f32,
+    #[prefab(component = crate::pig::PigPen)]
+    pig_pen: Entity,
+}
 
-impl Prefab for PlayerPrefab {
-    fn spawn_prfab(
-        &self,
-        fields: &HashMap<String, PrefabField>,
-        mut commands: EntityCommands,
-        asset_server: &AssetServer,
-    ) {
-        commands.insert(Name::new("Player".to_string()));
-
-        if let PrefabField::Number(speed) = fields
-            .get("speed")
-            .as_ref()
-            .expect("No speed value in player")
-        {
-            commands.insert(crate::Player {
-                speed: *speed as f32,
-            });
-        } else {
-            warn!("Speed value har wrong type")
-        }
+fn player_bundle() -> (Name, SpriteBundle) {
+    (Name::new("Player"), SpriteBundle::default())
+}
 
-        if let (PrefabField::String(sprite), PrefabField::Vec2(x, y)) = (
-            fields
-                .get("sprite")
-                .as_ref()
-                .expect("No sprite field on player"),
-            fields
-                .get("position")
-                .as_ref()
-                .expect("No position field on player"),
-        ) {
-            commands.insert(SpriteBundle {
-                texture: asset_server.load(sprite),
-                transform: Transform::from_translation(Vec3 {
-                    x: *x,
-                    y: *y,
-                    z: 0.0,
-                }),
-                ..default()
-            });
-        } else {
-            warn!("sprite or speed has wrong type");
-        }
+fn position_transform(position: Vec2) -> Transform {
+    Transform::from_translation(position.extend(0.0))
+}
+
+fn default_speed() -> f32 {
+    300.0
+}
+
+fn extract_player(entity: EntityRef) -> HashMap<String, PrefabField> {
+    let mut fields = HashMap::new();
+
+    if let Some(transform) = entity.get::<Transform>() {
+        fields.insert(
+            "position".to_string(),
+            PrefabField::Vec2(transform.translation.x, transform.translation.y),
+        );
     }
 
-    fn update_prfab(
-        &self,
-        changed_fields: &HashMap<String, PrefabField>,
-        asset_server: &AssetServer,
-        mut commands: EntityCommands,
-    ) {
-        changed_fields
-            .iter()
-            .map(|(name, field)| match name.as_str() {
-                "sprite" => {
-                    if let PrefabField::String(path) = field {
-                        commands.remove::<Handle<Image>>();
-
-                        let texture: Handle<Image> = asset_server.load(path);
-                        commands.insert(texture);
-                    } else {
-                        warn!("sprite filed expected string but got different type: {field:?}")
-                    }
-                }
-                "speed" => {
-                    if let PrefabField::Number(speed) = field {
-                        commands.remove::<crate::Player>();
-
-                        commands.insert(crate::Player {
-                            speed: *speed as f32,
-                        });
-                    } else {
-                        warn!("sprite filed expected string but got different type: {field:?}")
-                    }
-                }
-                _ => warn!("A player does not have a field named {}", name),
-            })
-            .count();
+    if let Some(player) = entity.get::<crate::Player>() {
+        fields.insert("speed".to_string(), PrefabField::Number(player.speed));
     }
+
+    fields
 }
 
 pub struct PigParentPrefab;
@@ -105,7 +73,7 @@ pub struct PigParentPrefab;
 impl Prefab for PigParentPrefab {
     fn spawn_prfab(
         &self,
-        _fields: &HashMap<String, PrefabField>,
+        _fields: &PrefabFields,
         mut commands: EntityCommands,
         _asset_server: &AssetServer,
     ) {
@@ -118,10 +86,51 @@ impl Prefab for PigParentPrefab {
 
     fn update_prfab(
         &self,
-        _changed_fields: &HashMap<String, PrefabField>,
+        _changes: &PrefabChangeSet,
         _asset_server: &AssetServer,
         _commands: EntityCommands,
     ) {
         // Nothing to update
     }
+
+    fn schema(&self) -> Option<PrefabSchema> {
+        Some(PrefabSchema::new())
+    }
+}
+
+pub struct MoneyResource;
+
+impl ResourcePrefab for MoneyResource {
+    fn insert_resource(
+        &self,
+        fields: &PrefabFields,
+        commands: &mut Commands,
+        _asset_server: &AssetServer,
+    ) {
+        match fields.require::<f32>("amount") {
+            Ok(amount) => commands.insert_resource(crate::Money(amount)),
+            Err(error) => warn!("{error}"),
+        }
+    }
+
+    fn update_resource(
+        &self,
+        changes: &PrefabChangeSet,
+        commands: &mut Commands,
+        _asset_server: &AssetServer,
+    ) {
+        match changes.changed().get_as::<f32>("amount") {
+            Ok(Some(amount)) => commands.insert_resource(crate::Money(amount)),
+            Ok(None) => {}
+            Err(error) => warn!("{error}"),
+        }
+    }
+
+    fn remove_resource(&self, commands: &mut Commands) {
+        commands.remove_resource::<crate::Money>();
+    }
+
+    fn schema(&self) -> Option<PrefabSchema> {
+        Some(PrefabSchema::new().required::<f32>("amount"))
+    }
 }
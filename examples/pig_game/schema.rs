@@ -0,0 +1,39 @@
+//! Writes a JSON Schema of the rooms of the pig game for editors, without opening a window.
+//!
+//! `cargo run --example pig_game_schema -- room.schema.json`
+
+use bevy::prelude::*;
+use components::{Money, Player};
+use hana_prefab::room::{PrefabRegistry, RoomPlugin};
+use prefab::DefaultPrefabsPlugin;
+
+mod components;
+#[allow(dead_code)]
+mod pig;
+mod prefab;
+
+fn main() {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("Usage: pig_game_schema <path>");
+        std::process::exit(2);
+    };
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        RoomPlugin,
+        DefaultPrefabsPlugin,
+    ));
+    app.update();
+
+    let schema = app.world.resource::<PrefabRegistry>().json_schema();
+    let schema = serde_json::to_string_pretty(&schema).expect("a JSON value can be serialized");
+    match std::fs::write(&path, schema) {
+        Ok(()) => println!("Wrote room schema to {path}"),
+        Err(error) => {
+            eprintln!("Could not write room schema to {path}: {error}");
+            std::process::exit(1);
+        }
+    }
+}
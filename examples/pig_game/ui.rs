@@ -10,7 +10,10 @@ struct MoneyText;
 impl Plugin for GameUiPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, spawn_ui_system);
-        app.add_systems(Update, update_money_ui_system);
+        app.add_systems(
+            Update,
+            update_money_ui_system.run_if(resource_exists::<Money>),
+        );
     }
 }
 
@@ -0,0 +1,480 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, ToTokens};
+use syn::{
+    parse_macro_input, Data, DeriveInput, ExprPath, Fields, GenericArgument, Ident, LitStr,
+    PathArguments, Type,
+};
+
+/// Derives the `Prefab` trait for a struct whose fields map to the fields of a room entry.
+///
+/// Every field is read from the room entry with the same name and inserted into the spawned
+/// entity as a component. When a field is changed in the room file only the component of that
+/// field is inserted again. Fields of type `Option<T>` are optional and only inserted when present,
+/// their component is removed again if the field is removed from the room file.
+/// The fields also make up the schema that rooms are validated against.
+///
+/// The following field attributes are supported:
+/// - `#[prefab(rename = "name")]` reads the field from a room field with a different name.
+/// - `#[prefab(default)]` uses `Default::default()` when the field is missing from the room.
+/// - `#[prefab(default = "path::to_fn")]` calls the given function when the field is missing.
+/// - `#[prefab(component = Type)]` inserts `Type::from(value)` instead of the field value itself.
+/// - `#[prefab(with = "path::to_fn")]` inserts the component returned by the function for the value.
+/// - `#[prefab(asset)]` loads a `Handle<T>` field from a path string using the asset server,
+///   the asset is also loaded as a dependency of the room.
+///
+/// The following attributes are supported on the struct:
+/// - `#[prefab(bundle = "path::to_fn")]` inserts the returned bundle before the fields when spawning.
+/// - `#[prefab(extract = "path::to_fn")]` reads the fields back from an entity when a room is saved.
+///
+/// The derive implements `DerivedPrefab`, so the prefab is registered by its type with
+/// `PrefabRegistry::register_derived` without building a value of the struct.
+///
+/// ```ignore
+/// #[derive(Prefab)]
+/// #[prefab(bundle = "SpriteBundle::default")]
+/// struct PlayerPrefab {
+///     #[prefab(component = Player)]
+///     speed: f32,
+///     #[prefab(asset, rename = "sprite")]
+///     texture: Handle<Image>,
+/// }
+///
+/// registry.register_derived::<PlayerPrefab>("Player");
+/// ```
+#[proc_macro_derive(Prefab, attributes(prefab))]
+pub fn derive_prefab(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_prefab(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// The parsed `#[prefab(...)]` attributes of the struct
+#[derive(Default)]
+struct PrefabAttributes {
+    bundle: Option<ExprPath>,
+    extract: Option<ExprPath>,
+}
+
+impl PrefabAttributes {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut attributes = Self::default();
+
+        for attr in input
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("prefab"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("bundle") {
+                    attributes.bundle = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                } else if meta.path.is_ident("extract") {
+                    attributes.extract = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                } else {
+                    return Err(meta.error("unknown prefab attribute"));
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(attributes)
+    }
+}
+
+/// The parsed `#[prefab(...)]` attributes of a single field
+struct PrefabFieldAttributes {
+    ident: Ident,
+    key: String,
+    ty: Type,
+    optional: bool,
+    default: Option<TokenStream2>,
+    component: Option<Type>,
+    with: Option<ExprPath>,
+    asset: bool,
+}
+
+impl PrefabFieldAttributes {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let ident = field
+            .ident
+            .clone()
+            .expect("named fields always have an ident");
+        let mut key = ident.to_string();
+        let mut default = None;
+        let mut component = None;
+        let mut with = None;
+        let mut asset = false;
+
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("prefab"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    key = meta.value()?.parse::<LitStr>()?.value();
+                } else if meta.path.is_ident("default") {
+                    default = Some(if meta.input.peek(syn::Token![=]) {
+                        let path: ExprPath = meta.value()?.parse::<LitStr>()?.parse()?;
+                        quote!(#path())
+                    } else {
+                        quote!(::core::default::Default::default())
+                    });
+                } else if meta.path.is_ident("component") {
+                    component = Some(meta.value()?.parse::<Type>()?);
+                } else if meta.path.is_ident("with") {
+                    with = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                } else if meta.path.is_ident("asset") {
+                    asset = true;
+                } else {
+                    return Err(meta.error("unknown prefab attribute"));
+                }
+                Ok(())
+            })?;
+        }
+
+        let (ty, optional) = match generic_inner_type(&field.ty, "Option") {
+            Some(inner) => (inner.clone(), true),
+            None => (field.ty.clone(), false),
+        };
+
+        if optional && default.is_some() {
+            return Err(syn::Error::new_spanned(
+                &field.ty,
+                "optional fields can not have a default value",
+            ));
+        }
+        if component.is_some() && with.is_some() {
+            return Err(syn::Error::new_spanned(
+                field,
+                "a field can not use both `component` and `with`",
+            ));
+        }
+        if optional && with.is_some() {
+            return Err(syn::Error::new_spanned(
+                field,
+                "optional fields can not use `with`, their component could not be removed",
+            ));
+        }
+
+        Ok(Self {
+            ident,
+            key,
+            ty,
+            optional,
+            default,
+            component,
+            with,
+            asset,
+        })
+    }
+
+    /// An expression reading the field from `fields` as a `Result<Option<T>, PrefabFieldError>`
+    fn read(&self, fields: &Ident) -> TokenStream2 {
+        let ty = &self.ty;
+        let key = &self.key;
+        if self.asset {
+            quote!(#fields
+                .get_as::<::std::string::String>(#key)
+                .map(|path| path.map(|path| -> #ty { asset_server.load(path) })))
+        } else {
+            quote!(#fields.get_as::<#ty>(#key))
+        }
+    }
+
+    /// The type the field is read as from the room
+    fn read_type(&self) -> TokenStream2 {
+        match self.asset {
+            true => quote!(::std::string::String),
+            false => self.ty.to_token_stream(),
+        }
+    }
+
+    /// An expression turning `value` into the component that is inserted
+    fn component(&self) -> TokenStream2 {
+        let ty = &self.ty;
+        match (&self.component, &self.with) {
+            (Some(component), _) => quote!(<#component as ::core::convert::From<#ty>>::from(value)),
+            (None, Some(with)) => quote!(#with(value)),
+            (None, None) => quote!(value),
+        }
+    }
+}
+
+fn expand_prefab(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields
+                .named
+                .iter()
+                .map(PrefabFieldAttributes::parse)
+                .collect::<syn::Result<Vec<_>>>()?,
+            Fields::Unit => Vec::new(),
+            Fields::Unnamed(_) => {
+                return Err(syn::Error::new_spanned(
+                    &input,
+                    "Prefab can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input,
+                "Prefab can only be derived for structs",
+            ))
+        }
+    };
+
+    let attributes = PrefabAttributes::parse(&input)?;
+    let prefab_name = name.to_string();
+
+    let fields_ident = Ident::new("fields", Span::call_site());
+    let read_fields = fields.iter().map(|field| {
+        let ident = &field.ident;
+        let read = field.read(&fields_ident);
+
+        let missing = if field.optional {
+            quote!(::core::option::Option::None)
+        } else if let Some(default) = &field.default {
+            quote!(#default)
+        } else {
+            let key = &field.key;
+            let ty = field.read_type();
+            quote!({
+                let error = ::hana_prefab::room::PrefabFieldError::Missing {
+                    prefab_key: fields.key().to_string(),
+                    field: #key.to_string(),
+                    expected: <#ty as ::hana_prefab::room::FromPrefabField>::EXPECTED,
+                };
+                ::hana_prefab::__private::warn!("{}", error);
+                return;
+            })
+        };
+
+        let wrong_type = if field.optional {
+            quote!(::core::option::Option::None)
+        } else {
+            quote!(return)
+        };
+
+        let found = if field.optional {
+            quote!(::core::option::Option::Some(value))
+        } else {
+            quote!(value)
+        };
+
+        quote! {
+            #ident: match #read {
+                ::core::result::Result::Ok(::core::option::Option::Some(value)) => #found,
+                ::core::result::Result::Ok(::core::option::Option::None) => #missing,
+                ::core::result::Result::Err(error) => {
+                    ::hana_prefab::__private::warn!("{}", error);
+                    #wrong_type
+                }
+            }
+        }
+    });
+
+    let insert_fields = fields.iter().map(|field| {
+        let ident = &field.ident;
+        let component = field.component();
+
+        if field.optional {
+            quote! {
+                if let ::core::option::Option::Some(value) = prefab.#ident {
+                    commands.insert(#component);
+                }
+            }
+        } else {
+            quote! {
+                let value = prefab.#ident;
+                commands.insert(#component);
+            }
+        }
+    });
+
+    let changed_fields_ident = Ident::new("changed_fields", Span::call_site());
+    let update_fields = fields.iter().map(|field| {
+        let key = &field.key;
+        let read = field.read(&changed_fields_ident);
+        let component = field.component();
+
+        quote! {
+            #key => match #read {
+                ::core::result::Result::Ok(::core::option::Option::Some(value)) => {
+                    commands.insert(#component);
+                }
+                ::core::result::Result::Ok(::core::option::Option::None) => {}
+                ::core::result::Result::Err(error) => {
+                    ::hana_prefab::__private::warn!("{}", error)
+                }
+            }
+        }
+    });
+
+    let remove_fields = fields.iter().map(|field| {
+        let key = &field.key;
+
+        let remove = if field.optional {
+            let component = field.component.as_ref().unwrap_or(&field.ty);
+            quote!({
+                commands.remove::<#component>();
+            })
+        } else if let Some(default) = &field.default {
+            let component = field.component();
+            quote!({
+                let value = #default;
+                commands.insert(#component);
+            })
+        } else {
+            quote!(::hana_prefab::__private::warn!(
+                "the required field {} of prefab {} was removed",
+                #key,
+                changes.key()
+            ))
+        };
+
+        quote!(#key => #remove)
+    });
+
+    let schema_fields = fields.iter().map(|field| {
+        let key = &field.key;
+        let required = !field.optional && field.default.is_none();
+
+        match (field.asset, generic_inner_type(&field.ty, "Handle")) {
+            (true, Some(asset)) if required => quote!(.required_asset::<#asset>(#key)),
+            (true, Some(asset)) => quote!(.optional_asset::<#asset>(#key)),
+            _ => {
+                let ty = field.read_type();
+                match required {
+                    true => quote!(.required::<#ty>(#key)),
+                    false => quote!(.optional::<#ty>(#key)),
+                }
+            }
+        }
+    });
+
+    let insert_bundle = attributes
+        .bundle
+        .map(|bundle| quote!(commands.insert(#bundle());));
+
+    let extract = attributes.extract.map(|extract| {
+        quote! {
+            fn extract(
+                entity: ::hana_prefab::__private::EntityRef,
+            ) -> ::hana_prefab::__private::HashMap<
+                ::std::string::String,
+                ::hana_prefab::room::PrefabField,
+            > {
+                #extract(entity)
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::hana_prefab::room::DerivedPrefab for #name #ty_generics #where_clause {
+            #[allow(unused_variables, unused_mut)]
+            fn spawn(
+                fields: &::hana_prefab::room::PrefabFields,
+                mut commands: ::hana_prefab::__private::EntityCommands,
+                asset_server: &::hana_prefab::__private::AssetServer,
+            ) {
+                let prefab = Self {
+                    #(#read_fields,)*
+                };
+                #insert_bundle
+                #(#insert_fields)*
+            }
+
+            #[allow(unused_variables, unused_mut)]
+            fn update(
+                changes: &::hana_prefab::room::PrefabChangeSet,
+                asset_server: &::hana_prefab::__private::AssetServer,
+                mut commands: ::hana_prefab::__private::EntityCommands,
+            ) {
+                let changed_fields = changes.changed();
+                for name in changed_fields.keys() {
+                    match name.as_str() {
+                        #(#update_fields)*
+                        _ => ::hana_prefab::__private::warn!(
+                            "prefab {} does not have a field named {}",
+                            #prefab_name,
+                            name
+                        ),
+                    }
+                }
+
+                for name in changes.removed().keys() {
+                    match name.as_str() {
+                        #(#remove_fields,)*
+                        _ => {}
+                    }
+                }
+            }
+
+            #extract
+
+            fn schema() -> ::hana_prefab::room::PrefabSchema {
+                ::hana_prefab::room::PrefabSchema::new()
+                    #(#schema_fields)*
+            }
+        }
+
+        impl #impl_generics ::hana_prefab::room::Prefab for #name #ty_generics #where_clause {
+            fn spawn_prfab(
+                &self,
+                fields: &::hana_prefab::room::PrefabFields,
+                commands: ::hana_prefab::__private::EntityCommands,
+                asset_server: &::hana_prefab::__private::AssetServer,
+            ) {
+                <Self as ::hana_prefab::room::DerivedPrefab>::spawn(fields, commands, asset_server)
+            }
+
+            fn update_prfab(
+                &self,
+                changes: &::hana_prefab::room::PrefabChangeSet,
+                asset_server: &::hana_prefab::__private::AssetServer,
+                commands: ::hana_prefab::__private::EntityCommands,
+            ) {
+                <Self as ::hana_prefab::room::DerivedPrefab>::update(changes, asset_server, commands)
+            }
+
+            fn extract(
+                &self,
+                entity: ::hana_prefab::__private::EntityRef,
+            ) -> ::hana_prefab::__private::HashMap<
+                ::std::string::String,
+                ::hana_prefab::room::PrefabField,
+            > {
+                <Self as ::hana_prefab::room::DerivedPrefab>::extract(entity)
+            }
+
+            fn schema(&self) -> ::core::option::Option<::hana_prefab::room::PrefabSchema> {
+                ::core::option::Option::Some(<Self as ::hana_prefab::room::DerivedPrefab>::schema())
+            }
+        }
+    })
+}
+
+/// Returns `T` if the type is `name<T>`, such as `Option<T>` or `Handle<T>`
+fn generic_inner_type<'a>(ty: &'a Type, name: &str) -> Option<&'a Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != name {
+        return None;
+    }
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return None;
+    };
+    match arguments.args.first()? {
+        GenericArgument::Type(inner) if arguments.args.len() == 1 => Some(inner),
+        _ => None,
+    }
+}
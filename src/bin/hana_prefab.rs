@@ -0,0 +1,12 @@
+//! Validates, lints, converts and formats room files, see [hana_prefab::cli] for the commands.
+//! No prefabs are registered, so rooms are not checked against prefab schemas.
+use std::process::ExitCode;
+
+use bevy::prelude::*;
+
+fn main() -> ExitCode {
+    // Only the types of the registry are needed, so no plugin that opens a window or loads assets is added
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, TransformPlugin, HierarchyPlugin));
+    hana_prefab::cli::run(&app.world, std::env::args().skip(1))
+}
@@ -0,0 +1,390 @@
+use bevy::{prelude::*, utils::HashMap};
+use indexmap::IndexMap;
+use ron::{extensions::Extensions, ser::PrettyConfig};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    format::RoomFormat,
+    reflect::component_from_ron,
+    room::{LoadRoomError, PrefabData, PrefabField, Room},
+};
+
+/// The version written into the header of binary rooms, rooms written with another version are rejected
+pub const BINARY_ROOM_VERSION: u16 = 1;
+
+/// The bytes every binary room starts with, followed by the version
+const MAGIC: &[u8; 4] = b"HROM";
+
+/// An error returned when a binary room could not be encoded or decoded
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum BinaryRoomError {
+    /// The data does not start with the header of a binary room
+    #[error("Not a binary room")]
+    MissingHeader,
+    /// The room was written with another version of the binary format
+    #[error("Unsupported binary room version {0}, expected version {BINARY_ROOM_VERSION}")]
+    UnsupportedVersion(u16),
+    /// A [bincode] Error
+    #[error("Could not encode binary room: {0}")]
+    Bincode(#[from] bincode::Error),
+    /// A reflected component could not be read from the RON it is stored as
+    #[error("Could not parse component {type_path}: {error}")]
+    Component {
+        type_path: String,
+        #[source]
+        error: ron::error::SpannedError,
+    },
+    /// A reflected component or the converted room could not be written as RON
+    #[error("Could not write RON: {0}")]
+    Ron(#[from] ron::Error),
+}
+
+/// Encodes a room written in RON as a binary room.
+///
+/// The room is stored the way it is written, its includes, variants and profiles are resolved
+/// when the binary room is loaded. Include paths are kept, so included rooms have to be converted
+/// separately if the includes should point at binary rooms.
+pub fn ron_to_binary(
+    ron: &[u8],
+    type_registry: &AppTypeRegistry,
+) -> Result<Vec<u8>, LoadRoomError> {
+    let room = RoomFormat::Ron.parse(ron, type_registry)?;
+    Ok(room_to_binary(&room)?)
+}
+
+/// Decodes a binary room back into RON
+pub fn binary_to_ron(
+    bytes: &[u8],
+    type_registry: &AppTypeRegistry,
+) -> Result<String, LoadRoomError> {
+    let room = room_from_binary(bytes, type_registry)?;
+    let ron =
+        ron::ser::to_string_pretty(&room, PrettyConfig::default()).map_err(BinaryRoomError::Ron)?;
+    Ok(ron)
+}
+
+pub(crate) fn room_to_binary(room: &Room) -> Result<Vec<u8>, BinaryRoomError> {
+    let binary_room = BinaryRoom {
+        includes: room.includes.clone(),
+        prefabs: encode_prefabs(&room.prefabs)?,
+        resources: encode_prefabs(&room.resources)?,
+    };
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(BINARY_ROOM_VERSION.to_le_bytes());
+    bincode::serialize_into(&mut bytes, &binary_room)?;
+    Ok(bytes)
+}
+
+pub(crate) fn room_from_binary(
+    bytes: &[u8],
+    type_registry: &AppTypeRegistry,
+) -> Result<Room, BinaryRoomError> {
+    let bytes = bytes
+        .strip_prefix(MAGIC)
+        .ok_or(BinaryRoomError::MissingHeader)?;
+    let (version, bytes) = match bytes {
+        [low, high, bytes @ ..] => (u16::from_le_bytes([*low, *high]), bytes),
+        _ => return Err(BinaryRoomError::MissingHeader),
+    };
+    if version != BINARY_ROOM_VERSION {
+        return Err(BinaryRoomError::UnsupportedVersion(version));
+    }
+
+    let binary_room: BinaryRoom = bincode::deserialize(bytes)?;
+    let options = ron::Options::default().with_default_extension(Extensions::IMPLICIT_SOME);
+    let decoder = Decoder {
+        options: &options,
+        type_registry,
+    };
+
+    Ok(Room {
+        includes: binary_room.includes,
+        prefabs: decoder.prefabs(binary_room.prefabs)?,
+        resources: decoder.prefabs(binary_room.resources)?,
+        settings: default(),
+        dependencies: Vec::new(),
+    })
+}
+
+/// The stored form of a [Room], maps are written as lists so no format features are needed
+#[derive(Serialize, Deserialize)]
+struct BinaryRoom {
+    includes: Vec<String>,
+    prefabs: Vec<(String, BinaryPrefab)>,
+    resources: Vec<(String, BinaryPrefab)>,
+}
+
+/// The stored form of [PrefabData], reflected components are stored as RON text
+/// because they can only be read through the type registry
+#[derive(Serialize, Deserialize)]
+struct BinaryPrefab {
+    prefab_type: String,
+    fields: Vec<(String, BinaryField)>,
+    components: Vec<(String, String)>,
+    children: Vec<(String, BinaryPrefab)>,
+    profiles: Vec<(String, BinaryPrefab)>,
+    extends: Option<String>,
+    template: bool,
+    order: Option<i32>,
+    after: Vec<String>,
+}
+
+/// The stored form of a [PrefabField], tagged so it can be read without knowing its shape
+#[derive(Serialize, Deserialize)]
+enum BinaryField {
+    Number(f32),
+    Bool(bool),
+    Vec2(f32, f32),
+    String(String),
+    Int(i64),
+    Float(f64),
+    Vec3(f32, f32, f32),
+    Vec4(f32, f32, f32, f32),
+    Quat(f32, f32, f32, f32),
+    Color(f32, f32, f32, f32),
+    List(Vec<BinaryField>),
+    Map(Vec<(String, BinaryField)>),
+    Option(Option<Box<BinaryField>>),
+    Ref(String),
+}
+
+fn encode_prefabs(
+    prefabs: &IndexMap<String, PrefabData>,
+) -> Result<Vec<(String, BinaryPrefab)>, BinaryRoomError> {
+    prefabs
+        .iter()
+        .map(|(name, prefab_data)| Ok((name.clone(), encode_prefab(prefab_data)?)))
+        .collect()
+}
+
+fn encode_prefab(prefab_data: &PrefabData) -> Result<BinaryPrefab, BinaryRoomError> {
+    let mut components = prefab_data
+        .components
+        .iter()
+        .map(|(type_path, component)| Ok((type_path.clone(), ron::to_string(component)?)))
+        .collect::<Result<Vec<_>, BinaryRoomError>>()?;
+    components.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    Ok(BinaryPrefab {
+        prefab_type: prefab_data.prefab_type.clone(),
+        fields: encode_fields(&prefab_data.fields),
+        components,
+        children: encode_prefabs(&prefab_data.children)?,
+        profiles: encode_prefabs(&prefab_data.profiles)?,
+        extends: prefab_data.extends.clone(),
+        template: prefab_data.template,
+        order: prefab_data.order,
+        after: prefab_data.after.clone(),
+    })
+}
+
+/// Fields are sorted by name so the same room is always encoded to the same bytes
+fn encode_fields(fields: &HashMap<String, PrefabField>) -> Vec<(String, BinaryField)> {
+    let mut fields: Vec<_> = fields
+        .iter()
+        .map(|(name, field)| (name.clone(), encode_field(field)))
+        .collect();
+    fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+    fields
+}
+
+fn encode_field(field: &PrefabField) -> BinaryField {
+    match field {
+        PrefabField::Number(value) => BinaryField::Number(*value),
+        PrefabField::Bool(value) => BinaryField::Bool(*value),
+        PrefabField::Vec2(x, y) => BinaryField::Vec2(*x, *y),
+        PrefabField::String(value) => BinaryField::String(value.clone()),
+        PrefabField::Int(value) => BinaryField::Int(*value),
+        PrefabField::Float(value) => BinaryField::Float(*value),
+        PrefabField::Vec3(x, y, z) => BinaryField::Vec3(*x, *y, *z),
+        PrefabField::Vec4(x, y, z, w) => BinaryField::Vec4(*x, *y, *z, *w),
+        PrefabField::Quat { x, y, z, w } => BinaryField::Quat(*x, *y, *z, *w),
+        PrefabField::Color { r, g, b, a } => BinaryField::Color(*r, *g, *b, *a),
+        PrefabField::List(list) => BinaryField::List(list.iter().map(encode_field).collect()),
+        PrefabField::Map(map) => BinaryField::Map(encode_fields(map)),
+        PrefabField::Option(option) => {
+            BinaryField::Option(option.as_deref().map(|field| Box::new(encode_field(field))))
+        }
+        PrefabField::Ref { prefab, .. } => BinaryField::Ref(prefab.clone()),
+    }
+}
+
+fn decode_field(field: BinaryField) -> PrefabField {
+    match field {
+        BinaryField::Number(value) => PrefabField::Number(value),
+        BinaryField::Bool(value) => PrefabField::Bool(value),
+        BinaryField::Vec2(x, y) => PrefabField::Vec2(x, y),
+        BinaryField::String(value) => PrefabField::String(value),
+        BinaryField::Int(value) => PrefabField::Int(value),
+        BinaryField::Float(value) => PrefabField::Float(value),
+        BinaryField::Vec3(x, y, z) => PrefabField::Vec3(x, y, z),
+        BinaryField::Vec4(x, y, z, w) => PrefabField::Vec4(x, y, z, w),
+        BinaryField::Quat(x, y, z, w) => PrefabField::Quat { x, y, z, w },
+        BinaryField::Color(r, g, b, a) => PrefabField::Color { r, g, b, a },
+        BinaryField::List(list) => PrefabField::List(list.into_iter().map(decode_field).collect()),
+        BinaryField::Map(map) => PrefabField::Map(decode_fields(map)),
+        BinaryField::Option(option) => {
+            PrefabField::Option(option.map(|field| Box::new(decode_field(*field))))
+        }
+        BinaryField::Ref(prefab) => PrefabField::Ref {
+            prefab,
+            entity: None,
+        },
+    }
+}
+
+fn decode_fields(fields: Vec<(String, BinaryField)>) -> HashMap<String, PrefabField> {
+    fields
+        .into_iter()
+        .map(|(name, field)| (name, decode_field(field)))
+        .collect()
+}
+
+/// Decodes prefabs, reading their components through the type registry
+struct Decoder<'a> {
+    options: &'a ron::Options,
+    type_registry: &'a AppTypeRegistry,
+}
+
+impl Decoder<'_> {
+    fn prefabs(
+        &self,
+        prefabs: Vec<(String, BinaryPrefab)>,
+    ) -> Result<IndexMap<String, PrefabData>, BinaryRoomError> {
+        prefabs
+            .into_iter()
+            .map(|(name, prefab)| Ok((name, self.prefab(prefab)?)))
+            .collect()
+    }
+
+    fn prefab(&self, prefab: BinaryPrefab) -> Result<PrefabData, BinaryRoomError> {
+        let components = prefab
+            .components
+            .into_iter()
+            .map(|(type_path, ron)| {
+                match component_from_ron(&type_path, &ron, self.options, self.type_registry) {
+                    Ok(component) => Ok((type_path, component)),
+                    Err(error) => Err(BinaryRoomError::Component { type_path, error }),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(PrefabData {
+            prefab_type: prefab.prefab_type,
+            fields: decode_fields(prefab.fields),
+            components,
+            children: self.prefabs(prefab.children)?,
+            profiles: self.prefabs(prefab.profiles)?,
+            extends: prefab.extends,
+            template: prefab.template,
+            order: prefab.order,
+            after: prefab.after,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::reflect::ReflectRef;
+
+    use super::*;
+
+    const ROOM: &str = r#"(
+        includes: ["rooms/base.ron"],
+        prefabs: {
+            "pig": (
+                type: "Pig",
+                fields: { "name": "pig", "speed": 2.5, "legs": 4, "pen": Ref(prefab: "pen") },
+                components: { "Transform": (translation: (x: 1.0), scale: (x: 2.0, y: 2.0, z: 2.0)) },
+                children: { "tail": (type: "Tail", order: 1, after: ["hat"]) },
+                profiles: { "hard": (fields: { "speed": 5.0 }) },
+            ),
+            "pen": (
+                type: "Pen",
+                template: true,
+                components: {
+                    "Transform": (
+                        translation: (x: 1.0, y: 2.0, z: 3.0),
+                        rotation: (x: 0.0, y: 0.0, z: 0.0, w: 1.0),
+                        scale: (x: 1.0, y: 1.0, z: 1.0),
+                    ),
+                },
+            ),
+            "hat": (extends: "pen", fields: { "color": (1.0, 0.0, 0.0, 1.0) }),
+        },
+        resources: { "money": (type: "Money", fields: { "amount": 100.0 }) },
+    )"#;
+
+    fn type_registry() -> AppTypeRegistry {
+        let type_registry = AppTypeRegistry::default();
+        let mut registry = type_registry.write();
+        registry.register::<Transform>();
+        registry.register::<Vec3>();
+        registry.register::<Quat>();
+        drop(registry);
+        type_registry
+    }
+
+    fn header(version: u16) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(version.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn ron_round_trip_keeps_the_room() {
+        let type_registry = type_registry();
+        let binary = ron_to_binary(ROOM.as_bytes(), &type_registry).unwrap();
+        let ron = binary_to_ron(&binary, &type_registry).unwrap();
+
+        let room = RoomFormat::Ron
+            .parse(ROOM.as_bytes(), &type_registry)
+            .unwrap();
+        let round_trip = RoomFormat::Ron
+            .parse(ron.as_bytes(), &type_registry)
+            .unwrap();
+        assert_eq!(round_trip.includes, room.includes);
+        assert_eq!(round_trip.prefabs, room.prefabs);
+        assert_eq!(round_trip.resources, room.resources);
+
+        assert_eq!(
+            ron_to_binary(ron.as_bytes(), &type_registry).unwrap(),
+            binary
+        );
+    }
+
+    #[test]
+    fn partial_components_stay_partial() {
+        let type_registry = type_registry();
+        let binary = ron_to_binary(ROOM.as_bytes(), &type_registry).unwrap();
+        let room = room_from_binary(&binary, &type_registry).unwrap();
+
+        let transform = room.prefabs["pig"].components["Transform"].value();
+        let ReflectRef::Struct(transform) = transform.reflect_ref() else {
+            panic!("Transform is a struct");
+        };
+        assert_eq!(transform.field_len(), 2);
+        assert!(transform.field("rotation").is_none());
+        let ReflectRef::Struct(translation) = transform.field("translation").unwrap().reflect_ref()
+        else {
+            panic!("Vec3 is a struct");
+        };
+        assert_eq!(translation.field_len(), 1);
+    }
+
+    #[test]
+    fn header_is_checked() {
+        let type_registry = type_registry();
+        assert!(matches!(
+            room_from_binary(b"(prefabs: {})", &type_registry),
+            Err(BinaryRoomError::MissingHeader)
+        ));
+        assert!(matches!(
+            room_from_binary(&header(BINARY_ROOM_VERSION + 1), &type_registry),
+            Err(BinaryRoomError::UnsupportedVersion(version)) if version == BINARY_ROOM_VERSION + 1
+        ));
+    }
+}
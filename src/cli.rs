@@ -0,0 +1,812 @@
+//! The `hana_prefab` command line tool, enabled with the `cli` feature.
+//!
+//! ```text
+//! hana_prefab [--assets <dir>] validate <files>...
+//! hana_prefab [--assets <dir>] lint <files>...
+//! hana_prefab [--assets <dir>] convert <input> <output>
+//! hana_prefab [--assets <dir>] fmt [--check] <files>...
+//! ```
+//!
+//! Rooms are read with the same code as the [RoomLoader](crate::room::RoomLoader),
+//! includes are read from the asset folder, `assets` unless `--assets` is given.
+//! The tool exits with code `1` if a room has errors or, for `lint` and `fmt --check`, warnings.
+//!
+//! `fmt` refuses to rewrite rooms that contain comments, because writing the room again would drop them.
+//!
+//! The `hana_prefab` binary only knows the components registered by bevy itself and has no
+//! [PrefabRegistry], so `validate` only checks the syntax, includes, variants and spawn order of rooms.
+//! Games whose rooms use their own components or should be checked against their prefab schemas
+//! can build their own binary that calls [run] with their plugins added:
+//!
+//! ```ignore
+//! fn main() -> ExitCode {
+//!     let mut app = App::new();
+//!     app.add_plugins((MinimalPlugins, AssetPlugin::default(), RoomPlugin, GamePrefabsPlugin));
+//!     hana_prefab::cli::run(&app.world, std::env::args().skip(1))
+//! }
+//! ```
+use std::{collections::HashSet, fmt, fs, io, path::PathBuf, process::ExitCode};
+
+use bevy::{prelude::*, tasks::block_on, utils::HashMap};
+use indexmap::IndexMap;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+use crate::{
+    format::RoomFormat,
+    room::{
+        child_key, include_path, load_include, read_room, FileIncludes, LoadRoomError, PrefabData,
+        PrefabField, PrefabRegistry, Room, RoomLoaderSettings,
+    },
+};
+
+const USAGE: &str = "\
+Usage: hana_prefab [--assets <dir>] <command>
+
+Commands:
+    validate <files>...        Report parse errors and, if prefabs are registered, schema violations
+    lint <files>...            Report duplicate keys, unused includes and suspicious field types
+    convert <input> <output>   Convert a room to the format of the output extension
+    fmt [--check] <files>...   Rewrite rooms without comments in their canonical formatting";
+
+const NO_PREFAB_REGISTRY: &str = "\
+No prefabs are registered, so validate only checks the syntax, includes, variants and spawn order
+of rooms and not their fields. Call hana_prefab::cli::run from a binary that registers the prefabs
+of the game to also check rooms against the prefab schemas.";
+
+/// Runs the command line tool with the given arguments, without the program name.
+///
+/// Components are read with the [AppTypeRegistry] of the world and rooms are validated
+/// against the [PrefabRegistry] if the world has one.
+pub fn run(world: &World, args: impl IntoIterator<Item = String>) -> ExitCode {
+    let mut assets = PathBuf::from("assets");
+    let mut check = false;
+    let mut positional = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--assets" => match args.next() {
+                Some(dir) => assets = PathBuf::from(dir),
+                None => return usage_error("--assets expects a directory"),
+            },
+            "--check" => check = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                if !world.contains_resource::<PrefabRegistry>() {
+                    println!("\n{NO_PREFAB_REGISTRY}");
+                }
+                return ExitCode::SUCCESS;
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let Some((command, files)) = positional.split_first() else {
+        return usage_error("missing command");
+    };
+
+    let cli = Cli {
+        assets,
+        type_registry: world.resource::<AppTypeRegistry>(),
+        prefab_registry: world.get_resource::<PrefabRegistry>(),
+    };
+
+    let problems = match (command.as_str(), files) {
+        ("validate", files) if !files.is_empty() => {
+            files.iter().map(|file| cli.validate(file)).sum()
+        }
+        ("lint", files) if !files.is_empty() => files.iter().map(|file| cli.lint(file)).sum(),
+        ("convert", [input, output]) => cli.convert(input, output),
+        ("fmt", files) if !files.is_empty() => files.iter().map(|file| cli.fmt(file, check)).sum(),
+        ("validate" | "lint" | "convert" | "fmt", _) => {
+            return usage_error(&format!("wrong arguments for {command}"))
+        }
+        _ => return usage_error(&format!("unknown command {command}")),
+    };
+
+    match problems {
+        0 => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    }
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("error: {message}\n\n{USAGE}");
+    ExitCode::from(2)
+}
+
+struct Cli<'w> {
+    assets: PathBuf,
+    type_registry: &'w AppTypeRegistry,
+    prefab_registry: Option<&'w PrefabRegistry>,
+}
+
+impl Cli<'_> {
+    /// Reads the room with its includes, variants and order resolved and reports every error.
+    /// Returns the number of errors.
+    fn validate(&self, file: &str) -> usize {
+        let Some(bytes) = read_file(file) else {
+            return 1;
+        };
+        let room = match self.read_room(file, &bytes) {
+            Ok(room) => room,
+            Err(error) => {
+                self.report_error(file, &bytes, &error);
+                return 1;
+            }
+        };
+
+        let Some(registry) = self.prefab_registry else {
+            return 0;
+        };
+
+        let mut errors = Vec::new();
+        unknown_types(None, &room.prefabs, &mut |key, prefab_type| {
+            if !registry.contains(prefab_type) {
+                errors.push(format!("Prefab {key} has the unknown type {prefab_type}"));
+            }
+        });
+        for (key, resource) in &room.resources {
+            if !registry
+                .resource_types()
+                .any(|resource_type| resource_type == resource.prefab_type)
+            {
+                errors.push(format!(
+                    "Resource {key} has the unknown type {}",
+                    resource.prefab_type
+                ));
+            }
+        }
+        errors.extend(
+            registry
+                .validate_room(&room)
+                .iter()
+                .map(ToString::to_string),
+        );
+
+        for error in &errors {
+            eprintln!("error: {file}: {error}");
+        }
+        errors.len()
+    }
+
+    /// Reports problems that do not stop the room from loading but are likely mistakes.
+    /// Returns the number of errors and warnings.
+    fn lint(&self, file: &str) -> usize {
+        let Some(bytes) = read_file(file) else {
+            return 1;
+        };
+        let format = RoomFormat::from_path(file);
+        let raw_room = match format.parse(&bytes, self.type_registry) {
+            Ok(room) => room,
+            Err(error) => {
+                self.report_error(file, &bytes, &error);
+                return 1;
+            }
+        };
+
+        // Reading the whole room first reports errors of includes in the included file
+        let unused_includes = self
+            .read_room(file, &bytes)
+            .and_then(|room| Ok((room, self.unused_includes(file, &raw_room)?)));
+        let (room, unused_includes) = match unused_includes {
+            Ok(result) => result,
+            Err(error) => {
+                self.report_error(file, &bytes, &error);
+                return 1;
+            }
+        };
+
+        let mut warnings = duplicate_keys(format, &bytes);
+        warnings.extend(unused_includes);
+        warnings.extend(suspicious_fields(&room));
+
+        for warning in &warnings {
+            eprintln!("warning: {file}: {warning}");
+        }
+        warnings.len()
+    }
+
+    /// Writes the room to the format of the output extension.
+    /// Includes are kept as they are and not converted.
+    fn convert(&self, input: &str, output: &str) -> usize {
+        let Some(bytes) = read_file(input) else {
+            return 1;
+        };
+        let room = match RoomFormat::from_path(input).parse(&bytes, self.type_registry) {
+            Ok(room) => room,
+            Err(error) => {
+                self.report_error(input, &bytes, &error);
+                return 1;
+            }
+        };
+
+        let result = RoomFormat::from_path(output)
+            .write(&room)
+            .and_then(|converted| Ok(fs::write(output, converted)?));
+        match result {
+            Ok(()) => {
+                println!("Converted {input} to {output}");
+                0
+            }
+            Err(error) => {
+                eprintln!("error: {output}: Could not write room: {error}");
+                1
+            }
+        }
+    }
+
+    /// Rewrites the room in the canonical formatting of its format, or only reports that it would with `check`.
+    /// Returns the number of files that could not be formatted or are not formatted with `check`.
+    fn fmt(&self, file: &str, check: bool) -> usize {
+        let Some(bytes) = read_file(file) else {
+            return 1;
+        };
+        let format = RoomFormat::from_path(file);
+        if format == RoomFormat::Binary {
+            eprintln!("error: {file}: Binary rooms can not be formatted");
+            return 1;
+        }
+
+        let room = match format.parse(&bytes, self.type_registry) {
+            Ok(room) => room,
+            Err(error) => {
+                self.report_error(file, &bytes, &error);
+                return 1;
+            }
+        };
+        if has_comments(format, &bytes) {
+            eprintln!("error: {file}: Rooms with comments can not be formatted, the comments would be lost");
+            return 1;
+        }
+
+        let formatted = match format.write(&room) {
+            Ok(formatted) => formatted,
+            Err(error) => {
+                eprintln!("error: {file}: Could not write room: {error}");
+                return 1;
+            }
+        };
+
+        if formatted == bytes {
+            return 0;
+        }
+        if check {
+            eprintln!("warning: {file}: Not formatted");
+            return 1;
+        }
+        match fs::write(file, formatted) {
+            Ok(()) => {
+                println!("Formatted {file}");
+                0
+            }
+            Err(error) => {
+                eprintln!("error: {file}: {error}");
+                1
+            }
+        }
+    }
+
+    fn read_room(&self, file: &str, bytes: &[u8]) -> Result<Room, LoadRoomError> {
+        block_on(read_room(
+            &self.asset_path(file)?,
+            bytes,
+            RoomFormat::from_path(file),
+            &RoomLoaderSettings::default(),
+            &mut FileIncludes(&self.assets),
+            self.type_registry,
+        ))
+    }
+
+    /// The path of the file relative to the asset folder, the way includes refer to it.
+    /// Both paths are canonicalized, so `./assets/..` and absolute paths are found in the asset folder.
+    fn asset_path(&self, file: &str) -> Result<String, LoadRoomError> {
+        let path = fs::canonicalize(file)?;
+        let assets = fs::canonicalize(&self.assets)?;
+        let path = path.strip_prefix(&assets).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{file} is not in the asset folder {}",
+                    self.assets.display()
+                ),
+            )
+        })?;
+        Ok(path.to_string_lossy().replace('\\', "/"))
+    }
+
+    /// Finds includes whose prefabs and resources are all replaced by later includes or the room itself
+    fn unused_includes(&self, file: &str, room: &Room) -> Result<Vec<String>, LoadRoomError> {
+        let mut included = Vec::new();
+        for include in &room.includes {
+            let mut include_stack = vec![include_path(&self.asset_path(file)?)];
+            let included_room = block_on(load_include(
+                include,
+                &mut FileIncludes(&self.assets),
+                self.type_registry,
+                &mut include_stack,
+            ))?;
+            included.push(room_keys(&included_room));
+        }
+
+        let mut warnings = Vec::new();
+        let mut later = room_keys(room);
+        for (include, keys) in room.includes.iter().zip(included).rev() {
+            if keys.is_subset(&later) {
+                warnings.push(format!(
+                    "Include {include} is unused, all of its entries are replaced"
+                ));
+            }
+            later.extend(keys);
+        }
+        warnings.reverse();
+        Ok(warnings)
+    }
+
+    /// Prints an error with the location and line it points at, errors of included rooms
+    /// are reported in the included file
+    fn report_error(&self, file: &str, source: &[u8], error: &LoadRoomError) {
+        if let LoadRoomError::Include { path, error } = error {
+            let include = self.assets.join(path);
+            let source = fs::read(&include).unwrap_or_default();
+            eprintln!("error: {file}: Could not load included room {path}");
+            return self.report_error(&include.to_string_lossy(), &source, error);
+        }
+
+        let source = String::from_utf8_lossy(source);
+        let message = match error {
+            LoadRoomError::RonSpannedError(error) => format!("Could not parse RON: {}", error.code),
+            LoadRoomError::Toml(error) => format!("Could not parse TOML: {}", error.message()),
+            error => error.to_string(),
+        };
+
+        match error_location(error, &source) {
+            Some((line, column)) => {
+                eprintln!("error: {file}:{line}:{column}: {message}");
+                print_snippet(&source, line, column);
+            }
+            None => eprintln!("error: {file}: {message}"),
+        }
+    }
+}
+
+fn read_file(file: &str) -> Option<Vec<u8>> {
+    fs::read(file)
+        .map_err(|error| eprintln!("error: {file}: {error}"))
+        .ok()
+}
+
+/// The line and column an error points at, both starting at 1
+fn error_location(error: &LoadRoomError, source: &str) -> Option<(usize, usize)> {
+    match error {
+        LoadRoomError::RonSpannedError(error) => Some((error.position.line, error.position.col)),
+        LoadRoomError::Json(error) if error.line() > 0 => Some((error.line(), error.column())),
+        LoadRoomError::Toml(error) => {
+            let before = source.get(..error.span()?.start)?;
+            let line = before.matches('\n').count() + 1;
+            let column = before.rsplit('\n').next()?.chars().count() + 1;
+            Some((line, column))
+        }
+        LoadRoomError::Yaml(error) => error
+            .location()
+            .map(|location| (location.line(), location.column())),
+        _ => None,
+    }
+}
+
+fn print_snippet(source: &str, line: usize, column: usize) {
+    let Some(text) = source.lines().nth(line.saturating_sub(1)) else {
+        return;
+    };
+    let number = line.to_string();
+    let gutter = " ".repeat(number.len());
+    eprintln!("{gutter} |");
+    eprintln!("{number} | {text}");
+    eprintln!("{gutter} | {}^", " ".repeat(column.saturating_sub(1)));
+}
+
+/// The keys of the top level prefabs and resources of a room
+fn room_keys(room: &Room) -> HashSet<String> {
+    let prefabs = room.prefabs.keys().map(|key| format!("prefab {key}"));
+    let resources = room.resources.keys().map(|key| format!("resource {key}"));
+    prefabs.chain(resources).collect()
+}
+
+fn unknown_types(
+    parent_key: Option<&str>,
+    prefabs: &IndexMap<String, PrefabData>,
+    report: &mut impl FnMut(&str, &str),
+) {
+    for (name, prefab_data) in prefabs {
+        let key = child_key(parent_key, name);
+        // Prefabs without a type only declare reflected components
+        if !prefab_data.prefab_type.is_empty() {
+            report(&key, &prefab_data.prefab_type);
+        }
+        unknown_types(Some(&key), &prefab_data.children, report);
+    }
+}
+
+/// Whether the room contains comments outside of strings, they are lost when the room is written again
+fn has_comments(format: RoomFormat, bytes: &[u8]) -> bool {
+    let markers: &[&str] = match format {
+        RoomFormat::Ron => &["//", "/*"],
+        RoomFormat::Toml | RoomFormat::Yaml => &["#"],
+        RoomFormat::Json | RoomFormat::Binary => return false,
+    };
+    let source = String::from_utf8_lossy(bytes);
+
+    let mut quote = None;
+    let mut escaped = false;
+    let mut previous = '\n';
+    for (index, c) in source.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            // TOML literal strings have no escapes
+            Some(open) if c == '\\' && (open == '"' || format == RoomFormat::Ron) => escaped = true,
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            // YAML only starts quoted strings and comments after whitespace or a separator
+            None if format == RoomFormat::Yaml
+                && !previous.is_whitespace()
+                && !"[{,:".contains(previous) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if markers
+                .iter()
+                .any(|marker| source[index..].starts_with(marker)) =>
+            {
+                return true
+            }
+            None => {}
+        }
+        previous = c;
+    }
+    false
+}
+
+/// Finds keys that are declared twice in the same map, most formats silently keep the last one
+fn duplicate_keys(format: RoomFormat, bytes: &[u8]) -> Vec<String> {
+    let mut duplicates = Vec::new();
+    let scan = KeyScan {
+        path: String::new(),
+        duplicates: &mut duplicates,
+    };
+
+    // The room was already parsed, so the scan does not fail on valid rooms
+    let _ = match format {
+        RoomFormat::Ron => ron::Options::default()
+            .from_bytes_seed(bytes, scan)
+            .map_err(|_| ()),
+        RoomFormat::Json => scan
+            .deserialize(&mut serde_json::Deserializer::from_slice(bytes))
+            .map_err(|_| ()),
+        RoomFormat::Toml => match std::str::from_utf8(bytes) {
+            Ok(text) => scan
+                .deserialize(toml::Deserializer::new(text))
+                .map_err(|_| ()),
+            Err(_) => Err(()),
+        },
+        RoomFormat::Yaml => scan
+            .deserialize(serde_yaml::Deserializer::from_slice(bytes))
+            .map_err(|_| ()),
+        RoomFormat::Binary => Ok(()),
+    };
+
+    duplicates
+        .into_iter()
+        .map(|path| format!("Duplicate key {path}, only the last one is used"))
+        .collect()
+}
+
+/// Walks any value of a room file and records the path of every repeated map key
+struct KeyScan<'a> {
+    path: String,
+    duplicates: &'a mut Vec<String>,
+}
+
+impl<'de> DeserializeSeed<'de> for KeyScan<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for KeyScan<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a room")
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_bytes<E: de::Error>(self, _: &[u8]) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let mut index = 0;
+        while seq
+            .next_element_seed(KeyScan {
+                path: format!("{}[{index}]", self.path),
+                duplicates: &mut *self.duplicates,
+            })?
+            .is_some()
+        {
+            index += 1;
+        }
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut keys = HashSet::new();
+        while let Some(KeyText(key)) = map.next_key()? {
+            let path = child_key((!self.path.is_empty()).then_some(&*self.path), &key);
+            if !keys.insert(key) {
+                self.duplicates.push(path.clone());
+            }
+            map.next_value_seed(KeyScan {
+                path,
+                duplicates: &mut *self.duplicates,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// A map key written as text, keys that are not scalars are written as `?`
+struct KeyText(String);
+
+impl<'de> de::Deserialize<'de> for KeyText {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeyTextVisitor;
+
+        impl<'de> Visitor<'de> for KeyTextVisitor {
+            type Value = KeyText;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map key")
+            }
+
+            fn visit_bool<E: de::Error>(self, value: bool) -> Result<KeyText, E> {
+                Ok(KeyText(value.to_string()))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<KeyText, E> {
+                Ok(KeyText(value.to_string()))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<KeyText, E> {
+                Ok(KeyText(value.to_string()))
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<KeyText, E> {
+                Ok(KeyText(value.to_string()))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<KeyText, E> {
+                Ok(KeyText(value.to_string()))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<KeyText, A::Error> {
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(KeyText("?".to_string()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<KeyText, A::Error> {
+                while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+                Ok(KeyText("?".to_string()))
+            }
+        }
+
+        deserializer.deserialize_any(KeyTextVisitor)
+    }
+}
+
+/// Finds fields whose value is likely written with the wrong type
+fn suspicious_fields(room: &Room) -> Vec<String> {
+    let mut lint = FieldLint::default();
+    lint.prefabs(None, &room.prefabs);
+    lint.prefabs(None, &room.resources);
+    lint.warnings
+}
+
+#[derive(Default)]
+struct FieldLint {
+    /// The kind of value and the key of the first prefab declaring each field of each type
+    kinds: HashMap<(String, String), (&'static str, String)>,
+    warnings: Vec<String>,
+}
+
+impl FieldLint {
+    fn prefabs(&mut self, parent_key: Option<&str>, prefabs: &IndexMap<String, PrefabData>) {
+        for (name, prefab_data) in prefabs {
+            let key = child_key(parent_key, name);
+
+            let mut fields: Vec<_> = prefab_data.fields.iter().collect();
+            fields.sort_by_key(|(field_name, _)| *field_name);
+            for (field_name, field) in fields {
+                self.field(&key, &prefab_data.prefab_type, field_name, field);
+            }
+
+            self.prefabs(Some(&key), &prefab_data.children);
+        }
+    }
+
+    fn field(&mut self, key: &str, prefab_type: &str, field_name: &str, field: &PrefabField) {
+        match field {
+            PrefabField::String(value)
+                if value.parse::<f64>().is_ok() || value.parse::<bool>().is_ok() =>
+            {
+                self.warnings.push(format!(
+                    "Field {field_name} of {key} is the string \"{value}\", it is read as a string and not as a number or bool"
+                ));
+            }
+            PrefabField::Map(map) if vector_map(map) => {
+                self.warnings.push(format!(
+                    "Field {field_name} of {key} is a map with the keys {}, it is read as a map and not as a vector written as ({})",
+                    sorted_keys(map),
+                    sorted_keys(map)
+                ));
+            }
+            _ => {}
+        }
+
+        let Some(kind) = field_kind(field) else {
+            return;
+        };
+        let declared = (prefab_type.to_string(), field_name.to_string());
+        match self.kinds.get(&declared) {
+            Some((first_kind, first_key)) if *first_kind != kind => {
+                self.warnings.push(format!(
+                    "Field {field_name} of {key} is a {kind}, but it is a {first_kind} in {first_key} of the same type {prefab_type}"
+                ));
+            }
+            Some(_) => {}
+            None => {
+                self.kinds.insert(declared, (kind, key.to_string()));
+            }
+        }
+    }
+}
+
+/// The kind of a field value, numbers are one kind as they convert into each other
+fn field_kind(field: &PrefabField) -> Option<&'static str> {
+    match field {
+        PrefabField::Number(_) | PrefabField::Int(_) | PrefabField::Float(_) => Some("Number"),
+        PrefabField::Option(None) => None,
+        PrefabField::Option(Some(field)) => field_kind(field),
+        field => Some(field.variant_name()),
+    }
+}
+
+/// Returns true for maps of numbers keyed like the components of a vector, such as `(x: 1, y: 2)`
+fn vector_map(map: &HashMap<String, PrefabField>) -> bool {
+    let keys = match map.len() {
+        2 => ["x", "y"].as_slice(),
+        3 => ["x", "y", "z"].as_slice(),
+        _ => return false,
+    };
+    keys.iter()
+        .all(|key| map.get(*key).and_then(PrefabField::as_f32).is_some())
+}
+
+fn sorted_keys(map: &HashMap<String, PrefabField>) -> String {
+    let mut keys: Vec<_> = map.keys().map(String::as_str).collect();
+    keys.sort();
+    keys.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_are_found_outside_of_strings() {
+        let cases = [
+            (RoomFormat::Ron, "(prefabs: {}) // comment", true),
+            (RoomFormat::Ron, "(prefabs: { /* pigs */ })", true),
+            (
+                RoomFormat::Ron,
+                r#"(prefabs: { "a": (type: "A", fields: { "url": "http://pig" }) })"#,
+                false,
+            ),
+            (
+                RoomFormat::Ron,
+                r#"(prefabs: { "a": (type: "A", fields: { "quote": "\" // " }) })"#,
+                false,
+            ),
+            (
+                RoomFormat::Toml,
+                "[prefabs.a]\ntype = \"A\" # comment",
+                true,
+            ),
+            (
+                RoomFormat::Toml,
+                "[prefabs.a]\ntype = \"#A\"\npath = 'C:\\'",
+                false,
+            ),
+            (RoomFormat::Yaml, "# comment\nprefabs: {}", true),
+            (
+                RoomFormat::Yaml,
+                "prefabs:\n  a:\n    type: A  # comment",
+                true,
+            ),
+            (
+                RoomFormat::Yaml,
+                "prefabs:\n  a:\n    type: Pig's#1\n    name: '#1'",
+                false,
+            ),
+            (
+                RoomFormat::Json,
+                r##"{ "prefabs": { "#": { "type": "//" } } }"##,
+                false,
+            ),
+        ];
+
+        for (format, source, expected) in cases {
+            assert_eq!(
+                has_comments(format, source.as_bytes()),
+                expected,
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn asset_paths_are_relative_to_the_asset_folder() {
+        let type_registry = AppTypeRegistry::default();
+        let cli = Cli {
+            assets: PathBuf::from("assets"),
+            type_registry: &type_registry,
+            prefab_registry: None,
+        };
+        let absolute = std::env::current_dir()
+            .unwrap()
+            .join("assets/rooms/test_room.ron");
+
+        for file in [
+            "assets/rooms/test_room.ron",
+            "./assets/rooms/test_room.ron",
+            &absolute.to_string_lossy(),
+        ] {
+            assert_eq!(cli.asset_path(file).unwrap(), "rooms/test_room.ron");
+        }
+        assert!(cli.asset_path("Cargo.toml").is_err());
+    }
+}
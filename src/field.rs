@@ -0,0 +1,1075 @@
+use std::{collections::BTreeMap, fmt, ops::Deref};
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+/// An enum used for determining type of a field.
+///
+/// Fields are parsed by their shape, so the names written in front of tuples and structs are optional:
+/// - every number is read as a [Number](PrefabField::Number). Values that need the full precision of
+///   an [Int](PrefabField::Int) or a [Float](PrefabField::Float) are written as `(i64: 9007199254740993)`
+///   or `(f64: 0.1)`, which is also how they are serialized. Fields are read as `f32`, `f64` or `i64`
+///   from any of the three with [FromPrefabField].
+/// - a tuple or list of two, three or four numbers is read as a vector, any other list as a [List](PrefabField::List).
+/// - a struct or map with the numeric fields `x`, `y`, `z` and `w` is read as a [Quat](PrefabField::Quat),
+///   one with the numeric fields `r`, `g`, `b` and optionally `a` as a [Color](PrefabField::Color),
+///   any other map as a [Map](PrefabField::Map).
+/// - `Some(...)` and `None` are read as an [Option](PrefabField::Option).
+/// - a struct or map with the single string field `prefab`, such as `Ref(prefab: "pig_parent")`,
+///   is read as a [Ref](PrefabField::Ref).
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum PrefabField {
+    Number(f32),
+    Bool(bool),
+    Vec2(f32, f32),
+    String(String),
+    #[serde(serialize_with = "serialize_int")]
+    Int(i64),
+    #[serde(serialize_with = "serialize_float")]
+    Float(f64),
+    Vec3(f32, f32, f32),
+    Vec4(f32, f32, f32, f32),
+    Quat {
+        x: f32,
+        y: f32,
+        z: f32,
+        w: f32,
+    },
+    Color {
+        r: f32,
+        g: f32,
+        b: f32,
+        a: f32,
+    },
+    List(Vec<PrefabField>),
+    Map(#[serde(serialize_with = "serialize_sorted")] HashMap<String, PrefabField>),
+    Option(Option<Box<PrefabField>>),
+    /// A reference to another prefab in the same room instance by its key,
+    /// the entity is filled in when the room is spawned or reloaded
+    Ref {
+        prefab: String,
+        #[serde(skip)]
+        entity: Option<Entity>,
+    },
+}
+
+impl PrefabField {
+    /// The name of the variant, used when reporting fields of the wrong type
+    pub fn variant_name(&self) -> &'static str {
+        match self {
+            PrefabField::Number(_) => "Number",
+            PrefabField::Bool(_) => "Bool",
+            PrefabField::Vec2(_, _) => "Vec2",
+            PrefabField::String(_) => "String",
+            PrefabField::Int(_) => "Int",
+            PrefabField::Float(_) => "Float",
+            PrefabField::Vec3(_, _, _) => "Vec3",
+            PrefabField::Vec4(_, _, _, _) => "Vec4",
+            PrefabField::Quat { .. } => "Quat",
+            PrefabField::Color { .. } => "Color",
+            PrefabField::List(_) => "List",
+            PrefabField::Map(_) => "Map",
+            PrefabField::Option(_) => "Option",
+            PrefabField::Ref { .. } => "Ref",
+        }
+    }
+
+    /// Prepends the prefix to the key of every reference in the field
+    pub(crate) fn prefix_refs(&mut self, prefix: &str) {
+        match self {
+            PrefabField::Ref { prefab, .. } => *prefab = format!("{prefix}{prefab}"),
+            PrefabField::List(list) => {
+                for field in list {
+                    field.prefix_refs(prefix);
+                }
+            }
+            PrefabField::Map(map) => {
+                for field in map.values_mut() {
+                    field.prefix_refs(prefix);
+                }
+            }
+            PrefabField::Option(Some(field)) => field.prefix_refs(prefix),
+            _ => {}
+        }
+    }
+
+    /// Removes the prefix from the key of every reference in the field that starts with it
+    pub(crate) fn strip_ref_prefix(&mut self, prefix: &str) {
+        match self {
+            PrefabField::Ref { prefab, .. } => {
+                if let Some(key) = prefab.strip_prefix(prefix) {
+                    *prefab = key.to_string();
+                }
+            }
+            PrefabField::List(list) => {
+                for field in list {
+                    field.strip_ref_prefix(prefix);
+                }
+            }
+            PrefabField::Map(map) => {
+                for field in map.values_mut() {
+                    field.strip_ref_prefix(prefix);
+                }
+            }
+            PrefabField::Option(Some(field)) => field.strip_ref_prefix(prefix),
+            _ => {}
+        }
+    }
+
+    /// Sets the entity of every reference in the field to the entity spawned for its key.
+    /// The keys of references that could not be resolved are added to `unresolved`.
+    pub(crate) fn resolve_refs(
+        &mut self,
+        entities: &HashMap<String, Entity>,
+        unresolved: &mut Vec<String>,
+    ) {
+        match self {
+            PrefabField::Ref { prefab, entity } => {
+                *entity = entities.get(prefab).copied();
+                if entity.is_none() {
+                    unresolved.push(prefab.clone());
+                }
+            }
+            PrefabField::List(list) => {
+                for field in list {
+                    field.resolve_refs(entities, unresolved);
+                }
+            }
+            PrefabField::Map(map) => {
+                for field in map.values_mut() {
+                    field.resolve_refs(entities, unresolved);
+                }
+            }
+            PrefabField::Option(Some(field)) => field.resolve_refs(entities, unresolved),
+            _ => {}
+        }
+    }
+
+    /// The value of a numeric field as an `f32`
+    pub(crate) fn as_f32(&self) -> Option<f32> {
+        match self {
+            PrefabField::Number(number) => Some(*number),
+            PrefabField::Float(number) => Some(*number as f32),
+            PrefabField::Int(number) => Some(*number as f32),
+            _ => None,
+        }
+    }
+
+    /// The value of a [Number](PrefabField::Number), explicit integers and floats are not read as
+    /// the components of vectors, rotations or colors so they keep their variant
+    fn as_number(&self) -> Option<f32> {
+        match self {
+            PrefabField::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    /// The elements of a list field, vectors are read as a list of numbers
+    fn elements(&self) -> Option<Vec<PrefabField>> {
+        let numbers = match self {
+            PrefabField::List(list) => return Some(list.clone()),
+            PrefabField::Vec2(x, y) => vec![*x, *y],
+            PrefabField::Vec3(x, y, z) => vec![*x, *y, *z],
+            PrefabField::Vec4(x, y, z, w) => vec![*x, *y, *z, *w],
+            _ => return None,
+        };
+        Some(numbers.into_iter().map(PrefabField::Number).collect())
+    }
+
+    /// The entries of a map field, quaternions and colors are read as a map of their components
+    fn entries(&self) -> Option<HashMap<String, PrefabField>> {
+        let components = match self {
+            PrefabField::Map(map) => return Some(map.clone()),
+            PrefabField::Quat { x, y, z, w } => [("x", *x), ("y", *y), ("z", *z), ("w", *w)],
+            PrefabField::Color { r, g, b, a } => [("r", *r), ("g", *g), ("b", *b), ("a", *a)],
+            PrefabField::Ref { prefab, .. } => {
+                let prefab = PrefabField::String(prefab.clone());
+                return Some(HashMap::from_iter([("prefab".to_string(), prefab)]));
+            }
+            _ => return None,
+        };
+        Some(
+            components
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), PrefabField::Number(value)))
+                .collect(),
+        )
+    }
+
+    /// Reads a map as a [Quat](PrefabField::Quat), [Color](PrefabField::Color)
+    /// or [Ref](PrefabField::Ref) if its keys match
+    fn from_entries(map: HashMap<String, PrefabField>) -> Self {
+        if let (1, Some(PrefabField::String(prefab))) = (map.len(), map.get("prefab")) {
+            return PrefabField::Ref {
+                prefab: prefab.clone(),
+                entity: None,
+            };
+        }
+
+        let number = |name: &str| map.get(name).and_then(PrefabField::as_number);
+        let has_keys = |names: &[&str]| {
+            map.len() == names.len() && names.iter().all(|name| number(name).is_some())
+        };
+
+        if has_keys(&["x", "y", "z", "w"]) {
+            PrefabField::Quat {
+                x: number("x").unwrap(),
+                y: number("y").unwrap(),
+                z: number("z").unwrap(),
+                w: number("w").unwrap(),
+            }
+        } else if has_keys(&["r", "g", "b", "a"]) || has_keys(&["r", "g", "b"]) {
+            PrefabField::Color {
+                r: number("r").unwrap(),
+                g: number("g").unwrap(),
+                b: number("b").unwrap(),
+                a: number("a").unwrap_or(1.0),
+            }
+        } else {
+            PrefabField::Map(map)
+        }
+    }
+
+    /// Reads a list of two to four numbers as a vector
+    fn from_elements(list: Vec<PrefabField>) -> Self {
+        let numbers: Option<Vec<f32>> = list.iter().map(PrefabField::as_number).collect();
+        match numbers.as_deref() {
+            Some(&[x, y]) => PrefabField::Vec2(x, y),
+            Some(&[x, y, z]) => PrefabField::Vec3(x, y, z),
+            Some(&[x, y, z, w]) => PrefabField::Vec4(x, y, z, w),
+            _ => PrefabField::List(list),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PrefabField {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(PrefabFieldVisitor)
+    }
+}
+
+/// Serializes a map sorted by its keys, so the same room is always written the same way
+pub(crate) fn serialize_sorted<S: Serializer, V: Serialize>(
+    map: &HashMap<String, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+/// Writes an [Int](PrefabField::Int) as `(i64: ...)`, so it is not read back as a Number
+fn serialize_int<S: Serializer>(value: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut state = serializer.serialize_struct("Int", 1)?;
+    state.serialize_field("i64", value)?;
+    state.end()
+}
+
+/// Writes a [Float](PrefabField::Float) as `(f64: ...)`, so it is not read back as a Number
+fn serialize_float<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut state = serializer.serialize_struct("Float", 1)?;
+    state.serialize_field("f64", value)?;
+    state.end()
+}
+
+struct PrefabFieldVisitor;
+
+impl<'de> Visitor<'de> for PrefabFieldVisitor {
+    type Value = PrefabField;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a prefab field")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+        Ok(PrefabField::Bool(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        Ok(PrefabField::Number(value as f32))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        Ok(PrefabField::Number(value as f32))
+    }
+
+    fn visit_f32<E: de::Error>(self, value: f32) -> Result<Self::Value, E> {
+        Ok(PrefabField::Number(value))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        Ok(PrefabField::Number(value as f32))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(PrefabField::String(value.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+        Ok(PrefabField::String(value))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(PrefabField::Option(None))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(PrefabField::Option(None))
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let value = PrefabField::deserialize(deserializer)?;
+        Ok(PrefabField::Option(Some(Box::new(value))))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut list = Vec::new();
+        while let Some(element) = seq.next_element()? {
+            list.push(element);
+        }
+        Ok(PrefabField::from_elements(list))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entries = HashMap::new();
+
+        // The value of `(i64: ...)` and `(f64: ...)` is read before it is rounded to a Number
+        if let Some(MapKey(key)) = map.next_key()? {
+            let value = match key.as_str() {
+                "i64" => PrefabField::Int(map.next_value()?),
+                "f64" => PrefabField::Float(map.next_value()?),
+                _ => map.next_value()?,
+            };
+            entries.insert(key, value);
+        }
+        while let Some((MapKey(key), value)) = map.next_entry()? {
+            entries.insert(key, value);
+        }
+
+        if entries.len() == 1 {
+            match entries.iter().next() {
+                Some((key, PrefabField::Int(_))) if key == "i64" => {
+                    return Ok(entries.into_values().next().unwrap())
+                }
+                Some((key, PrefabField::Float(_))) if key == "f64" => {
+                    return Ok(entries.into_values().next().unwrap())
+                }
+                _ => {}
+            }
+        }
+        Ok(PrefabField::from_entries(entries))
+    }
+}
+
+/// The key of a map or the name of a struct field, RON only allows reading the latter as a `str`
+struct MapKey(String);
+
+impl<'de> Deserialize<'de> for MapKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MapKeyVisitor;
+
+        impl Visitor<'_> for MapKeyVisitor {
+            type Value = MapKey;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a string key")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(MapKey(value.to_string()))
+            }
+        }
+
+        deserializer.deserialize_str(MapKeyVisitor)
+    }
+}
+
+/// A type that can be read from a [PrefabField]
+pub trait FromPrefabField: Sized {
+    /// The name of the type expected in the room, used in error messages
+    const EXPECTED: &'static str;
+
+    /// Converts the field, returns `None` if the field is of a different type
+    fn from_prefab_field(field: &PrefabField) -> Option<Self>;
+
+    /// The value used when the field is not declared in the room, `None` if the field is required
+    fn from_missing_field() -> Option<Self> {
+        None
+    }
+
+    /// Returns true if the field can be read as this type once the room is spawned,
+    /// used to validate rooms before their references are resolved
+    fn matches(field: &PrefabField) -> bool {
+        Self::from_prefab_field(field).is_some()
+    }
+
+    /// A JSON Schema describing the values that can be read as this type, used by editor tooling
+    fn json_schema() -> Value {
+        json!({})
+    }
+}
+
+/// A JSON Schema for a tuple of `count` numbers
+fn numbers_schema(count: usize) -> Value {
+    json!({
+        "type": "array",
+        "items": { "type": "number" },
+        "minItems": count,
+        "maxItems": count,
+    })
+}
+
+/// A JSON Schema for a struct of numbers, the `optional` names can be left out
+fn number_struct_schema(required: &[&str], optional: &[&str]) -> Value {
+    let properties: serde_json::Map<String, Value> = required
+        .iter()
+        .chain(optional)
+        .map(|name| (name.to_string(), json!({ "type": "number" })))
+        .collect();
+
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+impl FromPrefabField for f32 {
+    const EXPECTED: &'static str = "Number";
+
+    fn from_prefab_field(field: &PrefabField) -> Option<Self> {
+        field.as_f32()
+    }
+
+    fn json_schema() -> Value {
+        json!({ "type": "number" })
+    }
+}
+
+impl FromPrefabField for f64 {
+    const EXPECTED: &'static str = "Float";
+
+    fn from_prefab_field(field: &PrefabField) -> Option<Self> {
+        match field {
+            PrefabField::Float(number) => Some(*number),
+            PrefabField::Number(number) => Some(*number as f64),
+            PrefabField::Int(number) => Some(*number as f64),
+            _ => None,
+        }
+    }
+
+    fn json_schema() -> Value {
+        json!({ "anyOf": [{ "type": "number" }, tagged_number_schema("f64", "number")] })
+    }
+}
+
+impl FromPrefabField for i64 {
+    const EXPECTED: &'static str = "Int";
+
+    fn from_prefab_field(field: &PrefabField) -> Option<Self> {
+        // Decimals are accepted as long as they are whole numbers, so `300.0` can be read as `300`
+        let whole = |number: f64| {
+            (number.fract() == 0.0 && number >= i64::MIN as f64 && number < i64::MAX as f64)
+                .then_some(number as i64)
+        };
+        match field {
+            PrefabField::Int(number) => Some(*number),
+            PrefabField::Number(number) => whole(*number as f64),
+            PrefabField::Float(number) => whole(*number),
+            _ => None,
+        }
+    }
+
+    fn json_schema() -> Value {
+        json!({ "anyOf": [{ "type": "integer" }, tagged_number_schema("i64", "integer")] })
+    }
+}
+
+/// A JSON Schema of a number written with its full precision, such as `{ "i64": 300 }`
+fn tagged_number_schema(key: &str, number_type: &str) -> Value {
+    json!({
+        "type": "object",
+        "properties": { key: { "type": number_type } },
+        "required": [key],
+        "additionalProperties": false,
+    })
+}
+
+impl FromPrefabField for bool {
+    const EXPECTED: &'static str = "Bool";
+
+    fn from_prefab_field(field: &PrefabField) -> Option<Self> {
+        match field {
+            PrefabField::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn json_schema() -> Value {
+        json!({ "type": "boolean" })
+    }
+}
+
+impl FromPrefabField for String {
+    const EXPECTED: &'static str = "String";
+
+    fn from_prefab_field(field: &PrefabField) -> Option<Self> {
+        match field {
+            PrefabField::String(string) => Some(string.clone()),
+            _ => None,
+        }
+    }
+
+    fn json_schema() -> Value {
+        json!({ "type": "string" })
+    }
+}
+
+impl FromPrefabField for Vec2 {
+    const EXPECTED: &'static str = "Vec2";
+
+    fn from_prefab_field(field: &PrefabField) -> Option<Self> {
+        match field {
+            PrefabField::Vec2(x, y) => Some(Vec2::new(*x, *y)),
+            _ => None,
+        }
+    }
+
+    fn json_schema() -> Value {
+        numbers_schema(2)
+    }
+}
+
+impl FromPrefabField for Vec3 {
+    const EXPECTED: &'static str = "Vec3";
+
+    fn from_prefab_field(field: &PrefabField) -> Option<Self> {
+        match field {
+            PrefabField::Vec3(x, y, z) => Some(Vec3::new(*x, *y, *z)),
+            _ => None,
+        }
+    }
+
+    fn json_schema() -> Value {
+        numbers_schema(3)
+    }
+}
+
+impl FromPrefabField for Vec4 {
+    const EXPECTED: &'static str = "Vec4";
+
+    fn from_prefab_field(field: &PrefabField) -> Option<Self> {
+        match field {
+            PrefabField::Vec4(x, y, z, w) => Some(Vec4::new(*x, *y, *z, *w)),
+            _ => None,
+        }
+    }
+
+    fn json_schema() -> Value {
+        numbers_schema(4)
+    }
+}
+
+/// Reads a [Quat](PrefabField::Quat) or a `Vec4` with the components `(x, y, z, w)`
+impl FromPrefabField for Quat {
+    const EXPECTED: &'static str = "Quat";
+
+    fn from_prefab_field(field: &PrefabField) -> Option<Self> {
+        match field {
+            PrefabField::Quat { x, y, z, w } | PrefabField::Vec4(x, y, z, w) => {
+                Some(Quat::from_xyzw(*x, *y, *z, *w))
+            }
+            _ => None,
+        }
+    }
+
+    fn json_schema() -> Value {
+        json!({
+            "anyOf": [number_struct_schema(&["x", "y", "z", "w"], &[]), numbers_schema(4)]
+        })
+    }
+}
+
+/// Reads a [Color](PrefabField::Color), a `Vec3` or `Vec4` in sRGB or a hex string such as `"#ff8800"`
+impl FromPrefabField for Color {
+    const EXPECTED: &'static str = "Color";
+
+    fn from_prefab_field(field: &PrefabField) -> Option<Self> {
+        match field {
+            PrefabField::Color { r, g, b, a } | PrefabField::Vec4(r, g, b, a) => {
+                Some(Color::rgba(*r, *g, *b, *a))
+            }
+            PrefabField::Vec3(r, g, b) => Some(Color::rgb(*r, *g, *b)),
+            PrefabField::String(hex) => Color::hex(hex).ok(),
+            _ => None,
+        }
+    }
+
+    fn json_schema() -> Value {
+        json!({
+            "anyOf": [
+                number_struct_schema(&["r", "g", "b"], &["a"]),
+                numbers_schema(3),
+                numbers_schema(4),
+                { "type": "string", "pattern": "^#?([0-9a-fA-F]{3,4}|[0-9a-fA-F]{6}|[0-9a-fA-F]{8})$" },
+            ]
+        })
+    }
+}
+
+/// Reads a [Ref](PrefabField::Ref) as the entity spawned for the referenced prefab
+impl FromPrefabField for Entity {
+    const EXPECTED: &'static str = "Ref";
+
+    fn from_prefab_field(field: &PrefabField) -> Option<Self> {
+        match field {
+            PrefabField::Ref { entity, .. } => *entity,
+            _ => None,
+        }
+    }
+
+    fn matches(field: &PrefabField) -> bool {
+        matches!(field, PrefabField::Ref { .. })
+    }
+
+    fn json_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": { "prefab": { "type": "string" } },
+            "required": ["prefab"],
+            "additionalProperties": false,
+        })
+    }
+}
+
+/// Reads any field as it is, used for lists and maps with mixed types
+impl FromPrefabField for PrefabField {
+    const EXPECTED: &'static str = "PrefabField";
+
+    fn from_prefab_field(field: &PrefabField) -> Option<Self> {
+        Some(field.clone())
+    }
+}
+
+impl<T: FromPrefabField> FromPrefabField for Vec<T> {
+    const EXPECTED: &'static str = "List";
+
+    fn from_prefab_field(field: &PrefabField) -> Option<Self> {
+        field.elements()?.iter().map(T::from_prefab_field).collect()
+    }
+
+    fn matches(field: &PrefabField) -> bool {
+        field
+            .elements()
+            .is_some_and(|elements| elements.iter().all(T::matches))
+    }
+
+    fn json_schema() -> Value {
+        json!({ "type": "array", "items": T::json_schema() })
+    }
+}
+
+impl<T: FromPrefabField> FromPrefabField for HashMap<String, T> {
+    const EXPECTED: &'static str = "Map";
+
+    fn from_prefab_field(field: &PrefabField) -> Option<Self> {
+        field
+            .entries()?
+            .iter()
+            .map(|(key, value)| Some((key.clone(), T::from_prefab_field(value)?)))
+            .collect()
+    }
+
+    fn matches(field: &PrefabField) -> bool {
+        field
+            .entries()
+            .is_some_and(|entries| entries.values().all(T::matches))
+    }
+
+    fn json_schema() -> Value {
+        json!({ "type": "object", "additionalProperties": T::json_schema() })
+    }
+}
+
+impl<T: FromPrefabField> FromPrefabField for Option<T> {
+    const EXPECTED: &'static str = T::EXPECTED;
+
+    fn from_prefab_field(field: &PrefabField) -> Option<Self> {
+        match field {
+            PrefabField::Option(None) => Some(None),
+            PrefabField::Option(Some(field)) => T::from_prefab_field(field).map(Some),
+            field => T::from_prefab_field(field).map(Some),
+        }
+    }
+
+    fn from_missing_field() -> Option<Self> {
+        Some(None)
+    }
+
+    fn matches(field: &PrefabField) -> bool {
+        match field {
+            PrefabField::Option(None) => true,
+            PrefabField::Option(Some(field)) => T::matches(field),
+            field => T::matches(field),
+        }
+    }
+
+    fn json_schema() -> Value {
+        T::json_schema()
+    }
+}
+
+/// An error returned when a field of a prefab could not be read
+#[non_exhaustive]
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum PrefabFieldError {
+    /// A required field was not declared in the room
+    #[error("Prefab {prefab_key} is missing the field {field} of type {expected}")]
+    Missing {
+        prefab_key: String,
+        field: String,
+        expected: &'static str,
+    },
+    /// A field was declared with a different type than expected
+    #[error("Field {field} of prefab {prefab_key} expected {expected} but got {actual}")]
+    WrongType {
+        prefab_key: String,
+        field: String,
+        expected: &'static str,
+        actual: &'static str,
+    },
+    /// A field references a prefab that was not spawned in the same room instance
+    #[error("Field {field} of prefab {prefab_key} references the unknown prefab {target}")]
+    UnresolvedRef {
+        prefab_key: String,
+        field: String,
+        target: String,
+    },
+    /// A field is not declared in the schema of its prefab
+    #[error("Prefab {prefab_key} does not have a field named {field}")]
+    Unknown { prefab_key: String, field: String },
+}
+
+/// The fields of a single prefab in a room together with the key of the prefab.
+///
+/// Dereferences to the map of fields so they can still be matched by hand.
+#[derive(Debug, Clone, Copy)]
+pub struct PrefabFields<'a> {
+    key: &'a str,
+    fields: &'a HashMap<String, PrefabField>,
+}
+
+impl<'a> PrefabFields<'a> {
+    pub fn new(key: &'a str, fields: &'a HashMap<String, PrefabField>) -> Self {
+        Self { key, fields }
+    }
+
+    /// The key of the prefab in the room
+    pub fn key(&self) -> &'a str {
+        self.key
+    }
+
+    /// Reads a field as the type `T`, returns `Ok(None)` if the field is not declared
+    pub fn get_as<T: FromPrefabField>(&self, name: &str) -> Result<Option<T>, PrefabFieldError> {
+        let Some(field) = self.fields.get(name) else {
+            return Ok(None);
+        };
+
+        T::from_prefab_field(field)
+            .map(Some)
+            .ok_or_else(|| match field {
+                PrefabField::Ref {
+                    prefab,
+                    entity: None,
+                } => PrefabFieldError::UnresolvedRef {
+                    prefab_key: self.key.to_string(),
+                    field: name.to_string(),
+                    target: prefab.clone(),
+                },
+                field => PrefabFieldError::WrongType {
+                    prefab_key: self.key.to_string(),
+                    field: name.to_string(),
+                    expected: T::EXPECTED,
+                    actual: field.variant_name(),
+                },
+            })
+    }
+
+    /// Reads a field as the type `T`, returns an error if the field is not declared
+    pub fn require<T: FromPrefabField>(&self, name: &str) -> Result<T, PrefabFieldError> {
+        match self.get_as(name)? {
+            Some(value) => Ok(value),
+            None => T::from_missing_field().ok_or_else(|| PrefabFieldError::Missing {
+                prefab_key: self.key.to_string(),
+                field: name.to_string(),
+                expected: T::EXPECTED,
+            }),
+        }
+    }
+}
+
+impl Deref for PrefabFields<'_> {
+    type Target = HashMap<String, PrefabField>;
+
+    fn deref(&self) -> &Self::Target {
+        self.fields
+    }
+}
+
+/// The changes made to the fields of a single prefab when its room was reloaded
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrefabChangeSet {
+    key: String,
+    added: HashMap<String, PrefabField>,
+    modified: HashMap<String, (PrefabField, PrefabField)>,
+    removed: HashMap<String, PrefabField>,
+    changed: HashMap<String, PrefabField>,
+}
+
+impl PrefabChangeSet {
+    /// Compares the old and new fields of the prefab with the given key
+    pub fn new(
+        key: &str,
+        old_fields: &HashMap<String, PrefabField>,
+        new_fields: &HashMap<String, PrefabField>,
+    ) -> Self {
+        let mut added = HashMap::new();
+        let mut modified = HashMap::new();
+
+        for (name, new_field) in new_fields {
+            match old_fields.get(name) {
+                Some(old_field) if old_field != new_field => {
+                    modified.insert(name.clone(), (old_field.clone(), new_field.clone()));
+                }
+                Some(_) => {}
+                None => {
+                    added.insert(name.clone(), new_field.clone());
+                }
+            }
+        }
+
+        let removed: HashMap<String, PrefabField> = old_fields
+            .iter()
+            .filter(|(name, _)| !new_fields.contains_key(*name))
+            .map(|(name, field)| (name.clone(), field.clone()))
+            .collect();
+
+        let changed = added
+            .iter()
+            .map(|(name, field)| (name.clone(), field.clone()))
+            .chain(
+                modified
+                    .iter()
+                    .map(|(name, (_, new_field))| (name.clone(), new_field.clone())),
+            )
+            .collect();
+
+        Self {
+            key: key.to_string(),
+            added,
+            modified,
+            removed,
+            changed,
+        }
+    }
+
+    /// The key of the prefab in the room
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns true if no field was added, modified or removed
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    /// Fields that were not declared before the reload
+    pub fn added(&self) -> PrefabFields<'_> {
+        PrefabFields::new(&self.key, &self.added)
+    }
+
+    /// Fields whose value changed, stored as `(old, new)`
+    pub fn modified(&self) -> &HashMap<String, (PrefabField, PrefabField)> {
+        &self.modified
+    }
+
+    /// Fields that are no longer declared, with the value they had before the reload
+    pub fn removed(&self) -> PrefabFields<'_> {
+        PrefabFields::new(&self.key, &self.removed)
+    }
+
+    /// The new values of all added and modified fields
+    pub fn changed(&self) -> PrefabFields<'_> {
+        PrefabFields::new(&self.key, &self.changed)
+    }
+
+    /// Applies the changes to the fields they were computed from
+    pub fn apply(&self, fields: &mut HashMap<String, PrefabField>) {
+        fields.retain(|name, _| !self.removed.contains_key(name));
+        fields.extend(self.changed.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(ron: &str) -> PrefabField {
+        ron::from_str(ron).unwrap()
+    }
+
+    #[test]
+    fn numbers_are_read_as_numbers() {
+        assert_eq!(parse("300"), PrefabField::Number(300.0));
+        assert_eq!(parse("-3"), PrefabField::Number(-3.0));
+        assert_eq!(parse("300.0"), PrefabField::Number(300.0));
+        assert_eq!(parse("0.5"), PrefabField::Number(0.5));
+        assert_eq!(parse("0.1"), PrefabField::Number(0.1));
+    }
+
+    #[test]
+    fn full_precision_numbers_are_opt_in() {
+        assert_eq!(
+            parse("(i64: 9007199254740993)"),
+            PrefabField::Int(9007199254740993)
+        );
+        assert_eq!(parse("Float(f64: 0.1)"), PrefabField::Float(0.1));
+        assert!(ron::from_str::<PrefabField>("(i64: 0.5)").is_err());
+        assert_eq!(
+            parse(r#"{"i64": 1, "name": "a"}"#),
+            PrefabField::Map(HashMap::from_iter([
+                ("i64".to_string(), PrefabField::Int(1)),
+                ("name".to_string(), PrefabField::String("a".into())),
+            ]))
+        );
+    }
+
+    #[test]
+    fn numbers_keep_their_variant_when_written() {
+        for field in [
+            PrefabField::Number(0.1),
+            PrefabField::Number(300.0),
+            PrefabField::Int(9007199254740993),
+            PrefabField::Float(0.1),
+            PrefabField::List(vec![PrefabField::Number(0.1), PrefabField::Int(-2)]),
+        ] {
+            assert_eq!(parse(&ron::to_string(&field).unwrap()), field);
+            let json = serde_json::to_string(&field).unwrap();
+            assert_eq!(
+                serde_json::from_str::<PrefabField>(&json).unwrap(),
+                field,
+                "{json}"
+            );
+        }
+    }
+
+    #[test]
+    fn numeric_fields_are_read_as_any_number_type() {
+        for field in [
+            PrefabField::Int(3),
+            PrefabField::Number(3.0),
+            PrefabField::Float(3.0),
+        ] {
+            assert_eq!(f32::from_prefab_field(&field), Some(3.0));
+            assert_eq!(f64::from_prefab_field(&field), Some(3.0));
+            assert_eq!(i64::from_prefab_field(&field), Some(3));
+        }
+
+        assert_eq!(i64::from_prefab_field(&PrefabField::Number(3.5)), None);
+        assert_eq!(i64::from_prefab_field(&PrefabField::Float(1e300)), None);
+        assert_eq!(f32::from_prefab_field(&PrefabField::Bool(true)), None);
+    }
+
+    #[test]
+    fn lists_of_numbers_are_vectors() {
+        assert_eq!(parse("(1, 2)"), PrefabField::Vec2(1.0, 2.0));
+        assert_eq!(
+            parse("Vec3(1.0, 2.0, 3.0)"),
+            PrefabField::Vec3(1.0, 2.0, 3.0)
+        );
+        assert_eq!(parse("[1, 2, 3, 4]"), PrefabField::Vec4(1.0, 2.0, 3.0, 4.0));
+        assert_eq!(
+            parse("[1]"),
+            PrefabField::List(vec![PrefabField::Number(1.0)])
+        );
+        assert_eq!(
+            parse(r#"[1, "a"]"#),
+            PrefabField::List(vec![
+                PrefabField::Number(1.0),
+                PrefabField::String("a".into())
+            ])
+        );
+        assert_eq!(parse("[]"), PrefabField::List(Vec::new()));
+    }
+
+    #[test]
+    fn maps_are_read_by_their_keys() {
+        assert_eq!(
+            parse("Quat(x: 0.0, y: 0.0, z: 0.0, w: 1.0)"),
+            PrefabField::Quat {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+                w: 1.0
+            }
+        );
+        assert_eq!(
+            parse("(r: 1.0, g: 0.5, b: 0)"),
+            PrefabField::Color {
+                r: 1.0,
+                g: 0.5,
+                b: 0.0,
+                a: 1.0
+            }
+        );
+        assert_eq!(
+            parse(r#"Ref(prefab: "pig_parent")"#),
+            PrefabField::Ref {
+                prefab: "pig_parent".into(),
+                entity: None
+            }
+        );
+        assert_eq!(
+            parse(r#"{"x": 1.0, "name": "a"}"#),
+            PrefabField::Map(HashMap::from_iter([
+                ("x".to_string(), PrefabField::Number(1.0)),
+                ("name".to_string(), PrefabField::String("a".into())),
+            ]))
+        );
+        assert_eq!(
+            parse(r#"(r: 1.0, g: 0.5, b: "blue")"#).variant_name(),
+            "Map"
+        );
+    }
+
+    #[test]
+    fn other_values_keep_their_type() {
+        assert_eq!(parse("true"), PrefabField::Bool(true));
+        assert_eq!(parse(r#""pig""#), PrefabField::String("pig".into()));
+        assert_eq!(parse("None"), PrefabField::Option(None));
+        assert_eq!(
+            parse("Some(2)"),
+            PrefabField::Option(Some(Box::new(PrefabField::Number(2.0))))
+        );
+    }
+
+    #[test]
+    fn json_is_read_like_ron() {
+        let field: PrefabField = serde_json::from_str(
+            r#"{"position": [1, 2], "speed": 300.0, "target": {"prefab": "a"}}"#,
+        )
+        .unwrap();
+        let PrefabField::Map(map) = field else {
+            panic!("expected a map, got {field:?}");
+        };
+        assert_eq!(map["position"], PrefabField::Vec2(1.0, 2.0));
+        assert_eq!(map["speed"], PrefabField::Number(300.0));
+        assert_eq!(map["target"].variant_name(), "Ref");
+    }
+}
@@ -0,0 +1,183 @@
+use bevy::prelude::*;
+use ron::extensions::Extensions;
+
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+use crate::reflect::deserialize_components;
+use crate::{
+    reflect::components_from_ron,
+    room::{attach_components, LoadRoomError, Room},
+};
+
+/// The file formats rooms can be written in, every format except RON is behind a cargo feature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RoomFormat {
+    Ron,
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "toml")]
+    Toml,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    #[cfg(feature = "binary")]
+    Binary,
+}
+
+impl RoomFormat {
+    /// The format of an included room, read from its extension and falling back to RON
+    pub(crate) fn from_path(path: &str) -> Self {
+        let extension = path.rsplit('.').next().unwrap_or_default().to_lowercase();
+        match extension.as_str() {
+            #[cfg(feature = "json")]
+            "json" => Self::Json,
+            #[cfg(feature = "toml")]
+            "toml" => Self::Toml,
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Self::Yaml,
+            #[cfg(feature = "binary")]
+            "bin" => Self::Binary,
+            _ => Self::Ron,
+        }
+    }
+
+    /// Parses a room and the reflected components of its prefabs
+    pub(crate) fn parse(
+        self,
+        bytes: &[u8],
+        type_registry: &AppTypeRegistry,
+    ) -> Result<Room, LoadRoomError> {
+        let (mut room, components) = match self {
+            Self::Ron => {
+                // Optional values such as `extends` can be written without `Some(...)`
+                let options =
+                    ron::Options::default().with_default_extension(Extensions::IMPLICIT_SOME);
+                let room: Room = options.from_bytes(bytes)?;
+                (room, components_from_ron(bytes, &options, type_registry)?)
+            }
+            #[cfg(feature = "json")]
+            Self::Json => {
+                let room: Room = serde_json::from_slice(bytes)?;
+                let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+                (
+                    room,
+                    deserialize_components(&mut deserializer, type_registry)?,
+                )
+            }
+            #[cfg(feature = "toml")]
+            Self::Toml => {
+                let text = std::str::from_utf8(bytes)
+                    .map_err(<toml::de::Error as serde::de::Error>::custom)?;
+                let room: Room = toml::from_str(text)?;
+                (
+                    room,
+                    deserialize_components(toml::Deserializer::new(text), type_registry)?,
+                )
+            }
+            #[cfg(feature = "yaml")]
+            Self::Yaml => {
+                let room: Room = serde_yaml::from_slice(bytes)?;
+                (
+                    room,
+                    deserialize_components(
+                        serde_yaml::Deserializer::from_slice(bytes),
+                        type_registry,
+                    )?,
+                )
+            }
+            #[cfg(feature = "binary")]
+            Self::Binary => {
+                // Binary rooms store their components with the prefabs they belong to
+                return Ok(crate::binary::room_from_binary(bytes, type_registry)?);
+            }
+        };
+
+        attach_components(&mut room.prefabs, components);
+        Ok(room)
+    }
+
+    /// Writes a room in this format, text formats end with a newline
+    #[cfg(feature = "cli")]
+    pub(crate) fn write(self, room: &Room) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut text = match self {
+            Self::Ron => ron::ser::to_string_pretty(room, ron::ser::PrettyConfig::default())?,
+            Self::Json => serde_json::to_string_pretty(room)?,
+            Self::Toml => toml::to_string_pretty(room)?,
+            Self::Yaml => serde_yaml::to_string(room)?,
+            Self::Binary => return Ok(crate::binary::room_to_binary(room)?),
+        };
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        Ok(text.into_bytes())
+    }
+}
+
+/// Declares an asset loader that reads rooms in another format with the shared [RoomLoader]
+#[cfg(any(
+    feature = "json",
+    feature = "toml",
+    feature = "yaml",
+    feature = "binary"
+))]
+macro_rules! room_loader {
+    ($(#[$meta:meta])* $loader:ident, $format:expr, $extensions:expr) => {
+        $(#[$meta])*
+        pub struct $loader(crate::room::RoomLoader);
+
+        impl FromWorld for $loader {
+            fn from_world(world: &mut World) -> Self {
+                Self(crate::room::RoomLoader::from_world(world))
+            }
+        }
+
+        impl bevy::asset::AssetLoader for $loader {
+            type Asset = Room;
+            type Settings = crate::room::RoomLoaderSettings;
+            type Error = LoadRoomError;
+
+            fn extensions(&self) -> &[&str] {
+                $extensions
+            }
+
+            fn load<'a>(
+                &'a self,
+                reader: &'a mut bevy::asset::io::Reader,
+                settings: &'a Self::Settings,
+                load_context: &'a mut bevy::asset::LoadContext,
+            ) -> bevy::utils::BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+                Box::pin(self.0.load_room(reader, $format, settings, load_context))
+            }
+        }
+    };
+}
+
+#[cfg(feature = "json")]
+room_loader!(
+    /// The asset loader for rooms written in JSON, enabled with the `json` feature
+    JsonRoomLoader,
+    RoomFormat::Json,
+    &["room.json"]
+);
+
+#[cfg(feature = "toml")]
+room_loader!(
+    /// The asset loader for rooms written in TOML, enabled with the `toml` feature
+    TomlRoomLoader,
+    RoomFormat::Toml,
+    &["room.toml"]
+);
+
+#[cfg(feature = "yaml")]
+room_loader!(
+    /// The asset loader for rooms written in YAML, enabled with the `yaml` feature
+    YamlRoomLoader,
+    RoomFormat::Yaml,
+    &["room.yaml", "room.yml"]
+);
+
+#[cfg(feature = "binary")]
+room_loader!(
+    /// The asset loader for binary rooms, enabled with the `binary` feature
+    BinaryRoomLoader,
+    RoomFormat::Binary,
+    &["room.bin"]
+);
@@ -1 +1,25 @@
+#[cfg(feature = "binary")]
+pub mod binary;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod field;
+mod format;
+mod order;
+pub mod patch;
+pub mod progress;
+pub mod reflect;
 pub mod room;
+pub mod schema;
+mod spawner;
+mod variant;
+pub mod writer;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use bevy::{
+        asset::AssetServer,
+        ecs::{system::EntityCommands, world::EntityRef},
+        log::warn,
+        utils::HashMap,
+    };
+}
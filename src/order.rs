@@ -0,0 +1,207 @@
+use indexmap::IndexMap;
+
+use crate::room::{child_key, LoadRoomError, PrefabData, Room};
+
+/// Reorders the prefabs, children and resources of a room into the order they are spawned in.
+///
+/// Siblings are sorted by their `order` and keep the file order otherwise,
+/// an entry is then moved behind all siblings it is spawned `after`.
+pub(crate) fn sort_room(room: &mut Room) -> Result<(), LoadRoomError> {
+    sort_prefabs(None, &mut room.prefabs)?;
+    sort_prefabs(None, &mut room.resources)
+}
+
+fn sort_prefabs(
+    parent_key: Option<&str>,
+    prefabs: &mut IndexMap<String, PrefabData>,
+) -> Result<(), LoadRoomError> {
+    for (name, prefab_data) in prefabs.iter_mut() {
+        sort_prefabs(
+            Some(&child_key(parent_key, name)),
+            &mut prefab_data.children,
+        )?;
+    }
+
+    for (name, prefab_data) in prefabs.iter() {
+        if let Some(after) = prefab_data
+            .after
+            .iter()
+            .find(|after| !prefabs.contains_key(*after))
+        {
+            return Err(LoadRoomError::UnknownAfter {
+                key: child_key(parent_key, name),
+                after: after.clone(),
+            });
+        }
+    }
+
+    prefabs.sort_by(|_, a, _, b| a.order.unwrap_or(0).cmp(&b.order.unwrap_or(0)));
+
+    let mut remaining: Vec<String> = prefabs.keys().cloned().collect();
+    let mut sorted = Vec::with_capacity(remaining.len());
+    while !remaining.is_empty() {
+        let ready = remaining.iter().position(|name| {
+            prefabs[name]
+                .after
+                .iter()
+                .all(|after| !remaining.contains(after))
+        });
+
+        match ready {
+            Some(index) => sorted.push(remaining.remove(index)),
+            None => return Err(order_cycle(parent_key, prefabs, &remaining)),
+        }
+    }
+
+    let mut unsorted = std::mem::take(prefabs);
+    prefabs.extend(
+        sorted
+            .into_iter()
+            .filter_map(|name| unsorted.shift_remove_entry(&name)),
+    );
+
+    Ok(())
+}
+
+/// Follows the `after` keys of the entries that could not be sorted until one repeats
+fn order_cycle(
+    parent_key: Option<&str>,
+    prefabs: &IndexMap<String, PrefabData>,
+    remaining: &[String],
+) -> LoadRoomError {
+    let mut chain = vec![&remaining[0]];
+    loop {
+        let current = chain[chain.len() - 1];
+        let next = prefabs[current]
+            .after
+            .iter()
+            .find(|after| remaining.contains(after))
+            .expect("every remaining entry waits for another remaining entry");
+
+        let repeated = chain.contains(&next);
+        chain.push(next);
+        if repeated {
+            break;
+        }
+    }
+
+    // Entries that only lead into the cycle are not part of it
+    let start = chain
+        .iter()
+        .position(|name| *name == chain[chain.len() - 1])
+        .unwrap();
+    let chain: Vec<String> = chain[start..]
+        .iter()
+        .map(|name| child_key(parent_key, name))
+        .collect();
+    LoadRoomError::OrderCycle(chain.join(" -> "))
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::reflect::AppTypeRegistry;
+
+    use super::*;
+    use crate::format::RoomFormat;
+
+    fn sort(room: &str) -> Result<Room, LoadRoomError> {
+        let mut room = RoomFormat::Ron.parse(room.as_bytes(), &AppTypeRegistry::default())?;
+        sort_room(&mut room)?;
+        Ok(room)
+    }
+
+    fn keys(prefabs: &IndexMap<String, PrefabData>) -> Vec<&str> {
+        prefabs.keys().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn order_sorts_siblings_and_keeps_file_order() {
+        let room = sort(
+            r#"(prefabs: {
+                "a": (type: "A", order: 1),
+                "b": (type: "B"),
+                "c": (type: "C", order: -1),
+                "d": (type: "D", children: {
+                    "x": (type: "X", order: 2),
+                    "y": (type: "Y", order: 1),
+                }),
+                "e": (type: "E", order: 0),
+            })"#,
+        )
+        .unwrap();
+
+        assert_eq!(keys(&room.prefabs), ["c", "b", "d", "e", "a"]);
+        assert_eq!(keys(&room.prefabs["d"].children), ["y", "x"]);
+    }
+
+    #[test]
+    fn after_takes_precedence_over_order() {
+        let room = sort(
+            r#"(
+                prefabs: {
+                    "a": (type: "A", after: ["c"]),
+                    "b": (type: "B", order: 5),
+                    "c": (type: "C", after: ["b"]),
+                },
+                resources: {
+                    "money": (type: "Money", after: ["score"]),
+                    "score": (type: "Score"),
+                },
+            )"#,
+        )
+        .unwrap();
+
+        assert_eq!(keys(&room.prefabs), ["b", "c", "a"]);
+        assert_eq!(keys(&room.resources), ["score", "money"]);
+    }
+
+    #[test]
+    fn unknown_after_is_an_error() {
+        let error = sort(
+            r#"(prefabs: {
+                "pen": (type: "Pen", children: { "pig": (type: "Pig", after: ["pen"]) }),
+            })"#,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            LoadRoomError::UnknownAfter { key, after } if key == "pen/pig" && after == "pen"
+        ));
+    }
+
+    #[test]
+    fn order_cycle_only_names_the_entries_in_the_cycle() {
+        let error = sort(
+            r#"(prefabs: {
+                "pen": (type: "Pen", children: {
+                    "gate": (type: "Gate", after: ["a"]),
+                    "a": (type: "A", after: ["b"]),
+                    "b": (type: "B", after: ["a"]),
+                }),
+            })"#,
+        )
+        .unwrap_err();
+        let LoadRoomError::OrderCycle(cycle) = error else {
+            panic!("expected an order cycle, got {error:?}");
+        };
+        let keys: Vec<&str> = cycle.split(" -> ").collect();
+        assert_eq!(keys, ["pen/a", "pen/b", "pen/a"]);
+    }
+
+    #[test]
+    fn order_cycle_is_an_error() {
+        let error = sort(
+            r#"(prefabs: {
+                "a": (type: "A", after: ["b"]),
+                "b": (type: "B", after: ["c"]),
+                "c": (type: "C", after: ["a"]),
+            })"#,
+        )
+        .unwrap_err();
+        let LoadRoomError::OrderCycle(cycle) = error else {
+            panic!("expected an order cycle, got {error:?}");
+        };
+        let keys: Vec<&str> = cycle.split(" -> ").collect();
+        assert_eq!(keys, ["a", "b", "c", "a"]);
+    }
+}
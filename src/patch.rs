@@ -0,0 +1,486 @@
+use bevy::utils::HashMap;
+use indexmap::IndexMap;
+use thiserror::Error;
+
+use crate::{
+    reflect::ReflectedComponent,
+    room::{child_key, PrefabChangeSet, PrefabData, Room},
+};
+
+/// The changes between two versions of a room, computed with [Room::diff]
+/// and applied to the old version with [RoomPatch::apply].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RoomPatch {
+    /// The changes to the top level prefabs
+    pub prefabs: PrefabsPatch,
+    /// The changes to the resources
+    pub resources: PrefabsPatch,
+}
+
+/// The changes to the prefabs of a room or the children of a prefab
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrefabsPatch {
+    /// The entries that were added, removed, retyped or changed
+    pub changes: Vec<PrefabPatch>,
+    /// The names of all entries in their new order, only set if kept entries moved
+    /// or entries were added anywhere but the end
+    pub order: Option<Vec<String>>,
+}
+
+/// The change of a single entry between two versions of a room
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrefabPatch {
+    /// An entry that only exists in the new room
+    Added { name: String, data: PrefabData },
+    /// An entry that only exists in the old room, its children are removed with it
+    Removed { name: String },
+    /// An entry whose type changed, it is replaced together with its children
+    Retyped {
+        name: String,
+        old_type: String,
+        data: PrefabData,
+    },
+    /// An entry of the same type whose fields, components or children changed
+    Changed {
+        name: String,
+        fields: PrefabChangeSet,
+        components: ComponentChangeSet,
+        children: PrefabsPatch,
+    },
+}
+
+/// The changes made to the reflected components of a single entry
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ComponentChangeSet {
+    /// Components that were added or changed, with their new value
+    pub changed: HashMap<String, ReflectedComponent>,
+    /// Components that are no longer declared, with the value they had before
+    pub removed: HashMap<String, ReflectedComponent>,
+}
+
+/// An error returned when a patch does not fit the room it is applied to
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum ApplyPatchError {
+    /// The patch removes or changes an entry the room does not contain
+    #[error("The room has no entry {0} to patch")]
+    MissingEntry(String),
+    /// The patch adds an entry the room already contains
+    #[error("The room already contains the entry {0}")]
+    ExistingEntry(String),
+    /// The entries do not match the names of the new order after the patch was applied
+    #[error("The entries of {0} do not match the order of the patch")]
+    OrderMismatch(String),
+}
+
+impl Room {
+    /// Compares two versions of a room.
+    ///
+    /// Only what is spawned is compared: the type, fields, components and children of every entry
+    /// and the order of siblings. Rooms loaded by the [RoomLoader](crate::room::RoomLoader) already
+    /// have their includes, variants and profiles resolved. The `order`, `after`, `extends`, `template`
+    /// and `profiles` of kept entries are not compared, applying the patch keeps their old values.
+    ///
+    /// Hot reload applies the same patch to the spawned prefabs and inserted resources.
+    pub fn diff(old: &Room, new: &Room) -> RoomPatch {
+        RoomPatch {
+            prefabs: diff_prefabs(None, &old.prefabs, &new.prefabs),
+            resources: diff_prefabs(None, &old.resources, &new.resources),
+        }
+    }
+}
+
+impl RoomPatch {
+    /// Returns true if both versions of the room are spawned the same way
+    pub fn is_empty(&self) -> bool {
+        self.prefabs.is_empty() && self.resources.is_empty()
+    }
+
+    /// Applies the patch to the old version of the room it was computed from.
+    /// The room is left unchanged if the patch does not fit it.
+    pub fn apply(&self, room: &mut Room) -> Result<(), ApplyPatchError> {
+        let mut prefabs = room.prefabs.clone();
+        self.prefabs.apply(None, &mut prefabs)?;
+        let mut resources = room.resources.clone();
+        self.resources.apply(None, &mut resources)?;
+
+        room.prefabs = prefabs;
+        room.resources = resources;
+        Ok(())
+    }
+}
+
+impl PrefabsPatch {
+    /// Returns true if no entry changed or moved
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.order.is_none()
+    }
+
+    fn apply(
+        &self,
+        parent_key: Option<&str>,
+        prefabs: &mut IndexMap<String, PrefabData>,
+    ) -> Result<(), ApplyPatchError> {
+        for change in &self.changes {
+            let key = child_key(parent_key, change.name());
+            match change {
+                PrefabPatch::Added { name, data } => {
+                    if prefabs.contains_key(name) {
+                        return Err(ApplyPatchError::ExistingEntry(key));
+                    }
+                    prefabs.insert(name.clone(), data.clone());
+                }
+                PrefabPatch::Removed { name } => {
+                    prefabs
+                        .shift_remove(name)
+                        .ok_or(ApplyPatchError::MissingEntry(key))?;
+                }
+                PrefabPatch::Retyped { name, data, .. } => {
+                    let prefab_data = prefabs
+                        .get_mut(name)
+                        .ok_or(ApplyPatchError::MissingEntry(key))?;
+                    *prefab_data = data.clone();
+                }
+                PrefabPatch::Changed {
+                    name,
+                    fields,
+                    components,
+                    children,
+                } => {
+                    let prefab_data = prefabs
+                        .get_mut(name)
+                        .ok_or_else(|| ApplyPatchError::MissingEntry(key.clone()))?;
+                    fields.apply(&mut prefab_data.fields);
+                    components.apply(&mut prefab_data.components);
+                    children.apply(Some(&key), &mut prefab_data.children)?;
+                }
+            }
+        }
+
+        if let Some(order) = &self.order {
+            let mismatch = || ApplyPatchError::OrderMismatch(parent_key.unwrap_or("room").into());
+            if order.len() != prefabs.len() {
+                return Err(mismatch());
+            }
+            let mut unordered = std::mem::take(prefabs);
+            for name in order {
+                let (name, prefab_data) =
+                    unordered.shift_remove_entry(name).ok_or_else(mismatch)?;
+                prefabs.insert(name, prefab_data);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl PrefabPatch {
+    /// The name of the entry among its siblings
+    pub fn name(&self) -> &str {
+        match self {
+            PrefabPatch::Added { name, .. }
+            | PrefabPatch::Removed { name }
+            | PrefabPatch::Retyped { name, .. }
+            | PrefabPatch::Changed { name, .. } => name,
+        }
+    }
+}
+
+impl ComponentChangeSet {
+    /// Returns true if no component was added, changed or removed
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    /// Applies the changes to the components they were computed from
+    pub fn apply(&self, components: &mut HashMap<String, ReflectedComponent>) {
+        components.retain(|type_path, _| !self.removed.contains_key(type_path));
+        components.extend(self.changed.clone());
+    }
+}
+
+/// Compares the fields and components of two versions of an entry with the same type
+fn diff_contents(
+    key: &str,
+    old: &PrefabData,
+    new: &PrefabData,
+) -> (PrefabChangeSet, ComponentChangeSet) {
+    let fields = PrefabChangeSet::new(key, &old.fields, &new.fields);
+
+    let components = ComponentChangeSet {
+        changed: new
+            .components
+            .iter()
+            .filter(|(type_path, component)| old.components.get(*type_path) != Some(*component))
+            .map(|(type_path, component)| (type_path.clone(), component.clone()))
+            .collect(),
+        removed: old
+            .components
+            .iter()
+            .filter(|(type_path, _)| !new.components.contains_key(*type_path))
+            .map(|(type_path, component)| (type_path.clone(), component.clone()))
+            .collect(),
+    };
+
+    (fields, components)
+}
+
+/// Compares two versions of the prefabs of a room or the children of a prefab,
+/// hot reload applies the patch to the spawned prefabs and the inserted resources
+pub(crate) fn diff_prefabs(
+    parent_key: Option<&str>,
+    old: &IndexMap<String, PrefabData>,
+    new: &IndexMap<String, PrefabData>,
+) -> PrefabsPatch {
+    let mut changes = Vec::new();
+
+    for (name, new_prefab) in new {
+        let key = child_key(parent_key, name);
+        let change = match old.get(name) {
+            None => PrefabPatch::Added {
+                name: name.clone(),
+                data: new_prefab.clone(),
+            },
+            Some(old_prefab) if old_prefab.prefab_type != new_prefab.prefab_type => {
+                PrefabPatch::Retyped {
+                    name: name.clone(),
+                    old_type: old_prefab.prefab_type.clone(),
+                    data: new_prefab.clone(),
+                }
+            }
+            Some(old_prefab) => {
+                let (fields, components) = diff_contents(&key, old_prefab, new_prefab);
+                let children = diff_prefabs(Some(&key), &old_prefab.children, &new_prefab.children);
+                if fields.is_empty() && components.is_empty() && children.is_empty() {
+                    continue;
+                }
+                PrefabPatch::Changed {
+                    name: name.clone(),
+                    fields,
+                    components,
+                    children,
+                }
+            }
+        };
+        changes.push(change);
+    }
+
+    changes.extend(
+        old.keys()
+            .filter(|name| !new.contains_key(*name))
+            .map(|name| PrefabPatch::Removed { name: name.clone() }),
+    );
+
+    // Applying the changes keeps the order of the old entries and appends the added ones
+    let kept = old.keys().filter(|name| new.contains_key(*name));
+    let added = new.keys().filter(|name| !old.contains_key(*name));
+    let order = match kept.chain(added).eq(new.keys()) {
+        true => None,
+        false => Some(new.keys().cloned().collect()),
+    };
+
+    PrefabsPatch { changes, order }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::reflect::AppTypeRegistry;
+
+    use super::*;
+    use crate::{format::RoomFormat, room::PrefabField};
+
+    fn room(ron: &str) -> Room {
+        RoomFormat::Ron
+            .parse(ron.as_bytes(), &AppTypeRegistry::default())
+            .unwrap()
+    }
+
+    /// The keys of all entries and their children in the order they are spawned
+    fn keys(parent_key: Option<&str>, prefabs: &IndexMap<String, PrefabData>) -> Vec<String> {
+        prefabs
+            .iter()
+            .flat_map(|(name, prefab_data)| {
+                let key = child_key(parent_key, name);
+                let children = keys(Some(&key), &prefab_data.children);
+                std::iter::once(key).chain(children)
+            })
+            .collect()
+    }
+
+    /// Diffs the rooms and checks that applying the patch to the old room gives the new room
+    fn diff(old: &str, new: &str) -> RoomPatch {
+        let mut old = room(old);
+        let new = room(new);
+        let patch = Room::diff(&old, &new);
+        patch.apply(&mut old).unwrap();
+
+        assert_eq!(old.prefabs(), new.prefabs());
+        assert_eq!(old.resources(), new.resources());
+        assert_eq!(keys(None, old.prefabs()), keys(None, new.prefabs()));
+        assert_eq!(keys(None, old.resources()), keys(None, new.resources()));
+        patch
+    }
+
+    #[test]
+    fn same_rooms_have_an_empty_patch() {
+        let ron = r#"(prefabs: { "a": (type: "A", fields: { "x": 1.0 }) })"#;
+        assert!(diff(ron, ron).is_empty());
+    }
+
+    #[test]
+    fn added_and_removed_entries() {
+        let patch = diff(
+            r#"(prefabs: { "a": (type: "A"), "b": (type: "B") })"#,
+            r#"(prefabs: { "a": (type: "A"), "c": (type: "C") }, resources: { "money": (type: "Money") })"#,
+        );
+
+        let changes: Vec<_> = patch.prefabs.changes.iter().collect();
+        assert!(matches!(
+            changes[..],
+            [PrefabPatch::Added { name: added, data }, PrefabPatch::Removed { name: removed }]
+                if added == "c" && data.prefab_type == "C" && removed == "b"
+        ));
+        assert_eq!(patch.prefabs.order, None);
+        assert!(matches!(
+            &patch.resources.changes[..],
+            [PrefabPatch::Added { name, .. }] if name == "money"
+        ));
+    }
+
+    #[test]
+    fn retyped_entries_are_replaced() {
+        let patch = diff(
+            r#"(prefabs: { "a": (type: "A", children: { "x": (type: "X") }) })"#,
+            r#"(prefabs: { "a": (type: "B", fields: { "y": 2.0 }) })"#,
+        );
+
+        assert!(matches!(
+            &patch.prefabs.changes[..],
+            [PrefabPatch::Retyped { name, old_type, data }]
+                if name == "a" && old_type == "A" && data.prefab_type == "B"
+        ));
+    }
+
+    #[test]
+    fn nested_children_are_patched() {
+        let patch = diff(
+            r#"(prefabs: { "pen": (type: "Pen", children: {
+                "pig": (type: "Pig", fields: { "name": "pig" }, children: { "tail": (type: "Tail") }),
+            }) })"#,
+            r#"(prefabs: { "pen": (type: "Pen", children: {
+                "pig": (type: "Pig", fields: { "name": "piggy" }, children: { "hat": (type: "Hat") }),
+            }) })"#,
+        );
+
+        let [PrefabPatch::Changed {
+            name,
+            fields,
+            components,
+            children,
+        }] = &patch.prefabs.changes[..]
+        else {
+            panic!("pen is changed: {patch:?}");
+        };
+        assert_eq!(name, "pen");
+        assert!(fields.is_empty() && components.is_empty());
+
+        let [PrefabPatch::Changed {
+            name,
+            fields,
+            children,
+            ..
+        }] = &children.changes[..]
+        else {
+            panic!("pig is changed: {patch:?}");
+        };
+        assert_eq!(name, "pig");
+        assert_eq!(fields.key(), "pen/pig");
+        assert_eq!(
+            fields.modified()["name"],
+            (
+                PrefabField::String("pig".to_string()),
+                PrefabField::String("piggy".to_string())
+            )
+        );
+        assert!(matches!(
+            &children.changes[..],
+            [PrefabPatch::Added { name: added, .. }, PrefabPatch::Removed { name: removed }]
+                if added == "hat" && removed == "tail"
+        ));
+    }
+
+    #[test]
+    fn reordered_entries_have_an_order() {
+        let patch = diff(
+            r#"(prefabs: { "a": (type: "A"), "b": (type: "B"), "c": (type: "C") })"#,
+            r#"(prefabs: { "c": (type: "C"), "a": (type: "A"), "b": (type: "B") })"#,
+        );
+        assert!(patch.prefabs.changes.is_empty());
+        assert_eq!(
+            patch.prefabs.order,
+            Some(vec!["c".to_string(), "a".to_string(), "b".to_string()])
+        );
+
+        // Entries added before kept entries move them as well
+        let patch = diff(
+            r#"(prefabs: { "a": (type: "A") })"#,
+            r#"(prefabs: { "b": (type: "B"), "a": (type: "A") })"#,
+        );
+        assert_eq!(
+            patch.prefabs.order,
+            Some(vec!["b".to_string(), "a".to_string()])
+        );
+    }
+
+    #[test]
+    fn loading_metadata_is_not_patched() {
+        let old = room(r#"(prefabs: { "a": (type: "A"), "b": (type: "B", after: ["a"]) })"#);
+        let new = room(r#"(prefabs: { "a": (type: "A", order: -1), "b": (type: "B") })"#);
+        let patch = Room::diff(&old, &new);
+        assert!(patch.is_empty());
+
+        // The spawned room is the same, only the metadata of the old room is kept
+        let mut patched = old.clone();
+        patch.apply(&mut patched).unwrap();
+        assert_eq!(keys(None, patched.prefabs()), keys(None, new.prefabs()));
+        assert_eq!(patched.prefabs()["a"].order, None);
+        assert_eq!(patched.prefabs()["b"].after, ["a"]);
+        assert!(Room::diff(&patched, &new).is_empty());
+    }
+
+    #[test]
+    fn patches_that_do_not_fit_leave_the_room_unchanged() {
+        let reorder = Room::diff(
+            &room(r#"(prefabs: { "a": (type: "A"), "b": (type: "B") })"#),
+            &room(r#"(prefabs: { "b": (type: "B"), "a": (type: "A") })"#),
+        );
+        let mut other =
+            room(r#"(prefabs: { "a": (type: "A"), "b": (type: "B"), "c": (type: "C") })"#);
+        assert!(matches!(
+            reorder.apply(&mut other),
+            Err(ApplyPatchError::OrderMismatch(key)) if key == "room"
+        ));
+        assert_eq!(keys(None, other.prefabs()), ["a", "b", "c"]);
+
+        let remove = Room::diff(
+            &room(r#"(prefabs: { "pen": (type: "Pen", children: { "pig": (type: "Pig") }) })"#),
+            &room(r#"(prefabs: { "pen": (type: "Pen") })"#),
+        );
+        let mut other = room(r#"(prefabs: { "pen": (type: "Pen") })"#);
+        assert!(matches!(
+            remove.apply(&mut other),
+            Err(ApplyPatchError::MissingEntry(key)) if key == "pen/pig"
+        ));
+
+        let add = Room::diff(
+            &room(r#"(prefabs: {})"#),
+            &room(r#"(prefabs: { "a": (type: "A") })"#),
+        );
+        let mut other = room(r#"(prefabs: { "a": (type: "B") })"#);
+        assert!(matches!(
+            add.apply(&mut other),
+            Err(ApplyPatchError::ExistingEntry(key)) if key == "a"
+        ));
+        assert_eq!(other.prefabs()["a"].prefab_type, "B");
+    }
+}
@@ -0,0 +1,70 @@
+use bevy::{asset::RecursiveDependencyLoadState, ecs::system::SystemParam, prelude::*};
+
+use crate::room::Room;
+
+/// How much of a room and the assets its asset path fields refer to has loaded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RoomLoadProgress {
+    /// True once the room file itself, including its includes, is loaded
+    pub room_loaded: bool,
+    /// The number of dependencies that finished loading, failed dependencies are counted as finished
+    pub loaded: usize,
+    /// The number of dependencies of the room, only known once the room is loaded
+    pub total: usize,
+}
+
+impl RoomLoadProgress {
+    /// The loaded part between `0.0` and `1.0`, the room file counts as one of the assets
+    pub fn fraction(&self) -> f32 {
+        let loaded = self.loaded + usize::from(self.room_loaded);
+        loaded as f32 / (self.total + 1) as f32
+    }
+
+    /// Returns true if the room and all of its dependencies finished loading
+    pub fn is_done(&self) -> bool {
+        self.room_loaded && self.loaded == self.total
+    }
+}
+
+/// A system parameter that reports the loading progress of rooms, for example to show a loading screen.
+///
+/// ```ignore
+/// fn loading_screen(progress: RoomProgress, room: Query<&Handle<Room>>) {
+///     let progress = progress.get(room.single());
+///     info!("{:.0}%", progress.fraction() * 100.0);
+/// }
+/// ```
+#[derive(SystemParam)]
+pub struct RoomProgress<'w> {
+    rooms: Res<'w, Assets<Room>>,
+    asset_server: Res<'w, AssetServer>,
+}
+
+impl RoomProgress<'_> {
+    /// The loading progress of the given room
+    pub fn get(&self, room: impl Into<AssetId<Room>>) -> RoomLoadProgress {
+        let Some(room) = self.rooms.get(room.into()) else {
+            return RoomLoadProgress::default();
+        };
+
+        let loaded = room
+            .dependencies
+            .iter()
+            .filter(|handle| {
+                matches!(
+                    self.asset_server
+                        .get_recursive_dependency_load_state(handle.id()),
+                    Some(
+                        RecursiveDependencyLoadState::Loaded | RecursiveDependencyLoadState::Failed
+                    )
+                )
+            })
+            .count();
+
+        RoomLoadProgress {
+            room_loaded: true,
+            loaded,
+            total: room.dependencies.len(),
+        }
+    }
+}
@@ -0,0 +1,468 @@
+use std::{any::TypeId, fmt};
+
+use bevy::{
+    ecs::{reflect::AppTypeRegistry, world::EntityRef},
+    prelude::*,
+    reflect::{
+        serde::{TypedReflectDeserializer, TypedReflectSerializer},
+        std_traits::ReflectDefault,
+        ReflectFromReflect, ReflectRef, TypeInfo, TypeRegistration, TypeRegistry,
+    },
+    utils::HashMap,
+};
+use serde::{
+    de::{self, DeserializeSeed, IgnoredAny, MapAccess, Visitor},
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::patch::ComponentChangeSet;
+
+/// A component declared in the `components` map of a room entry.
+///
+/// The value is deserialized through the [AppTypeRegistry], so any component registered with
+/// `#[reflect(Component)]` can be used. Fields that are left out keep their current value on hot
+/// reload and use the default value of the component when it is inserted, they are also left out
+/// when the component is serialized.
+pub struct ReflectedComponent {
+    type_id: TypeId,
+    value: Box<dyn Reflect>,
+    registry: AppTypeRegistry,
+}
+
+impl ReflectedComponent {
+    /// The reflected value as it was declared in the room
+    pub fn value(&self) -> &dyn Reflect {
+        &*self.value
+    }
+
+    /// Reads the current value of the component from a spawned entity
+    pub(crate) fn extract(&self, entity: EntityRef) -> Option<Self> {
+        let registry = self.registry.read();
+        let value = registry
+            .get_type_data::<ReflectComponent>(self.type_id)?
+            .reflect(entity)?;
+
+        Some(Self {
+            type_id: self.type_id,
+            value: value.clone_value(),
+            registry: self.registry.clone(),
+        })
+    }
+}
+
+impl Clone for ReflectedComponent {
+    fn clone(&self) -> Self {
+        Self {
+            type_id: self.type_id,
+            value: self.value.clone_value(),
+            registry: self.registry.clone(),
+        }
+    }
+}
+
+impl PartialEq for ReflectedComponent {
+    fn eq(&self, other: &Self) -> bool {
+        self.type_id == other.type_id
+            && self
+                .value
+                .reflect_partial_eq(&*other.value)
+                .unwrap_or(false)
+    }
+}
+
+impl fmt::Debug for ReflectedComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl Serialize for ReflectedComponent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let registry = self.registry.read();
+        PartialSerializer {
+            value: &*self.value,
+            registry: &registry,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Serializes a reflected value the way it was declared,
+/// fields that were left out of a struct are left out of the output as well
+struct PartialSerializer<'a> {
+    value: &'a dyn Reflect,
+    registry: &'a TypeRegistry,
+}
+
+impl Serialize for PartialSerializer<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if let (ReflectRef::Struct(value), Some(TypeInfo::Struct(info))) = (
+            self.value.reflect_ref(),
+            self.value.get_represented_type_info(),
+        ) {
+            if is_partial(self.value) {
+                let mut state = serializer.serialize_struct(
+                    info.type_path_table().ident().unwrap_or_default(),
+                    value.field_len(),
+                )?;
+                for (index, field) in value.iter_fields().enumerate() {
+                    let name = value.name_at(index).and_then(|name| info.field(name));
+                    let Some(name) = name.map(|field_info| field_info.name()) else {
+                        continue;
+                    };
+                    let field = PartialSerializer {
+                        value: field,
+                        registry: self.registry,
+                    };
+                    state.serialize_field(name, &field)?;
+                }
+                return state.end();
+            }
+        }
+
+        // Dynamic values are converted to their type first, their fields may be in any order
+        let value = self.value.get_represented_type_info().and_then(|info| {
+            self.registry
+                .get_type_data::<ReflectFromReflect>(info.type_id())
+                .and_then(|from_reflect| from_reflect.from_reflect(self.value))
+        });
+        match value {
+            Some(value) => {
+                TypedReflectSerializer::new(&*value, self.registry).serialize(serializer)
+            }
+            None => TypedReflectSerializer::new(self.value, self.registry).serialize(serializer),
+        }
+    }
+}
+
+/// Whether fields were left out of the value or of any struct it holds
+fn is_partial(value: &dyn Reflect) -> bool {
+    match (value.reflect_ref(), value.get_represented_type_info()) {
+        (ReflectRef::Struct(value), Some(TypeInfo::Struct(info))) => {
+            value.field_len() < info.field_len() || value.iter_fields().any(is_partial)
+        }
+        _ => false,
+    }
+}
+
+/// The reflected components of the prefabs in a room file, mirroring the nesting of the prefabs
+#[derive(Default)]
+pub(crate) struct ComponentTree {
+    pub(crate) components: HashMap<String, ReflectedComponent>,
+    pub(crate) children: HashMap<String, ComponentTree>,
+    pub(crate) profiles: HashMap<String, ComponentTree>,
+}
+
+/// Reads the `components` maps of all prefabs in a room file.
+///
+/// Components can only be deserialized with access to the type registry,
+/// so they are read in a second pass over the room file with a [DeserializeSeed].
+pub(crate) fn components_from_ron(
+    bytes: &[u8],
+    options: &ron::Options,
+    type_registry: &AppTypeRegistry,
+) -> Result<HashMap<String, ComponentTree>, ron::error::SpannedError> {
+    let registry = type_registry.read();
+    options.from_bytes_seed(
+        bytes,
+        RoomSeed(ReflectContext {
+            type_registry,
+            registry: &registry,
+        }),
+    )
+}
+
+/// Reads the `components` maps of all prefabs from a room in any self-describing format
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+pub(crate) fn deserialize_components<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    type_registry: &AppTypeRegistry,
+) -> Result<HashMap<String, ComponentTree>, D::Error> {
+    let registry = type_registry.read();
+    RoomSeed(ReflectContext {
+        type_registry,
+        registry: &registry,
+    })
+    .deserialize(deserializer)
+}
+
+/// Reads a single component from its RON value, used by formats that store components as RON text
+#[cfg(feature = "binary")]
+pub(crate) fn component_from_ron(
+    type_path: &str,
+    ron: &str,
+    options: &ron::Options,
+    type_registry: &AppTypeRegistry,
+) -> Result<ReflectedComponent, ron::error::SpannedError> {
+    let registry = type_registry.read();
+    options.from_str_seed(
+        ron,
+        ComponentSeed(
+            ReflectContext {
+                type_registry,
+                registry: &registry,
+            },
+            type_path,
+        ),
+    )
+}
+
+/// Inserts the components into the entity, components it already has are patched in place
+pub(crate) fn insert_components(
+    components: Vec<ReflectedComponent>,
+) -> impl FnOnce(EntityWorldMut) + Send + 'static {
+    move |mut entity: EntityWorldMut| {
+        let Some(component) = components.first() else {
+            return;
+        };
+        let registry = component.registry.clone();
+        let registry = registry.read();
+
+        for component in &components {
+            if let Some(reflect_component) =
+                registry.get_type_data::<ReflectComponent>(component.type_id)
+            {
+                reflect_component.apply_or_insert(&mut entity, &*component.value, &registry);
+            }
+        }
+    }
+}
+
+/// Returns a command that applies the changed components of a reloaded prefab
+/// and removes the components that are no longer declared, or `None` if nothing changed
+pub(crate) fn patch_components(
+    changes: &ComponentChangeSet,
+) -> Option<impl FnOnce(EntityWorldMut) + Send + 'static> {
+    if changes.is_empty() {
+        return None;
+    }
+
+    let changed: Vec<ReflectedComponent> = changes.changed.values().cloned().collect();
+    let removed: Vec<ReflectedComponent> = changes.removed.values().cloned().collect();
+    Some(move |mut entity: EntityWorldMut| {
+        for component in &removed {
+            let registry = component.registry.read();
+            if let Some(reflect_component) =
+                registry.get_type_data::<ReflectComponent>(component.type_id)
+            {
+                reflect_component.remove(&mut entity);
+            }
+        }
+
+        insert_components(changed)(entity);
+    })
+}
+
+#[derive(Clone, Copy)]
+struct ReflectContext<'a> {
+    type_registry: &'a AppTypeRegistry,
+    registry: &'a TypeRegistry,
+}
+
+impl ReflectContext<'_> {
+    /// Looks up a component by its full type path or, if it is unambiguous, its short type path
+    fn component_registration(&self, type_path: &str) -> Result<&TypeRegistration, String> {
+        let registration = self
+            .registry
+            .get_with_type_path(type_path)
+            .or_else(|| self.registry.get_with_short_type_path(type_path))
+            .ok_or_else(|| format!("no type registered with the type path {type_path}"))?;
+
+        if registration.data::<ReflectComponent>().is_none() {
+            return Err(format!(
+                "{type_path} is not reflected as a component, add #[reflect(Component)] to it"
+            ));
+        }
+
+        Ok(registration)
+    }
+}
+
+/// The name of a struct field
+struct Identifier(String);
+
+impl<'de> Deserialize<'de> for Identifier {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct IdentifierVisitor;
+
+        impl Visitor<'_> for IdentifierVisitor {
+            type Value = Identifier;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an identifier")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(Identifier(value.to_string()))
+            }
+        }
+
+        deserializer.deserialize_identifier(IdentifierVisitor)
+    }
+}
+
+struct RoomSeed<'a>(ReflectContext<'a>);
+
+impl<'de> DeserializeSeed<'de> for RoomSeed<'_> {
+    type Value = HashMap<String, ComponentTree>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct("Room", &["includes", "prefabs", "resources"], self)
+    }
+}
+
+impl<'de> Visitor<'de> for RoomSeed<'_> {
+    type Value = HashMap<String, ComponentTree>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a room")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut prefabs = HashMap::new();
+        while let Some(Identifier(key)) = map.next_key()? {
+            match key.as_str() {
+                "prefabs" => prefabs = map.next_value_seed(PrefabMapSeed(self.0))?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(prefabs)
+    }
+}
+
+struct PrefabMapSeed<'a>(ReflectContext<'a>);
+
+impl<'de> DeserializeSeed<'de> for PrefabMapSeed<'_> {
+    type Value = HashMap<String, ComponentTree>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for PrefabMapSeed<'_> {
+    type Value = HashMap<String, ComponentTree>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of prefabs")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut prefabs = HashMap::new();
+        while let Some(name) = map.next_key::<String>()? {
+            let tree = map.next_value_seed(PrefabSeed(self.0))?;
+            prefabs.insert(name, tree);
+        }
+        Ok(prefabs)
+    }
+}
+
+struct PrefabSeed<'a>(ReflectContext<'a>);
+
+impl<'de> DeserializeSeed<'de> for PrefabSeed<'_> {
+    type Value = ComponentTree;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct(
+            "PrefabData",
+            &[
+                "type",
+                "fields",
+                "components",
+                "children",
+                "profiles",
+                "extends",
+                "template",
+                "order",
+                "after",
+            ],
+            self,
+        )
+    }
+}
+
+impl<'de> Visitor<'de> for PrefabSeed<'_> {
+    type Value = ComponentTree;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a prefab")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut tree = ComponentTree::default();
+        while let Some(Identifier(key)) = map.next_key()? {
+            match key.as_str() {
+                "components" => tree.components = map.next_value_seed(ComponentsSeed(self.0))?,
+                "children" => tree.children = map.next_value_seed(PrefabMapSeed(self.0))?,
+                "profiles" => tree.profiles = map.next_value_seed(PrefabMapSeed(self.0))?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(tree)
+    }
+}
+
+struct ComponentsSeed<'a>(ReflectContext<'a>);
+
+impl<'de> DeserializeSeed<'de> for ComponentsSeed<'_> {
+    type Value = HashMap<String, ReflectedComponent>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ComponentsSeed<'_> {
+    type Value = HashMap<String, ReflectedComponent>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of component type paths and values")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut components = HashMap::new();
+        while let Some(type_path) = map.next_key::<String>()? {
+            let component = map.next_value_seed(ComponentSeed(self.0, &type_path))?;
+            components.insert(type_path, component);
+        }
+        Ok(components)
+    }
+}
+
+/// Reads the value of a single component with the given type path
+struct ComponentSeed<'a>(ReflectContext<'a>, &'a str);
+
+impl<'de> DeserializeSeed<'de> for ComponentSeed<'_> {
+    type Value = ReflectedComponent;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let ComponentSeed(context, type_path) = self;
+        let registration = context
+            .component_registration(type_path)
+            .map_err(de::Error::custom)?;
+
+        let value = TypedReflectDeserializer::new(registration, context.registry)
+            .deserialize(deserializer)?;
+
+        let insertable = registration.data::<ReflectDefault>().is_some()
+            || registration
+                .data::<ReflectFromReflect>()
+                .is_some_and(|from_reflect| from_reflect.from_reflect(&*value).is_some());
+        if !insertable {
+            return Err(de::Error::custom(format!(
+                "component {type_path} is missing fields and has no #[reflect(Default)]"
+            )));
+        }
+
+        Ok(ReflectedComponent {
+            type_id: registration.type_id(),
+            value,
+            registry: context.type_registry.clone(),
+        })
+    }
+}
@@ -1,14 +1,52 @@
+use std::{
+    marker::PhantomData,
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
 use bevy::{
-    asset::{AssetLoader, AsyncReadExt},
-    ecs::system::EntityCommands,
+    asset::{
+        AssetLoader, AssetPath, AsyncReadExt, LoadContext, ReadAssetBytesError, UntypedHandle,
+    },
+    ecs::{system::EntityCommands, world::EntityRef},
     prelude::*,
     reflect::TypePath,
-    utils::{HashMap, HashSet},
+    tasks::block_on,
+    utils::{BoxedFuture, HashMap},
 };
 
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::{
+    field::serialize_sorted,
+    format::RoomFormat,
+    order::sort_room,
+    reflect::ComponentTree,
+    schema::{room_json_schema, validate_room, AssetFields},
+    spawner::{room_system, RoomTracker},
+    variant::{apply_profile, resolve_variants},
+};
+
+pub use crate::field::{
+    FromPrefabField, PrefabChangeSet, PrefabField, PrefabFieldError, PrefabFields,
+};
+#[cfg(feature = "binary")]
+pub use crate::format::BinaryRoomLoader;
+#[cfg(feature = "json")]
+pub use crate::format::JsonRoomLoader;
+#[cfg(feature = "toml")]
+pub use crate::format::TomlRoomLoader;
+#[cfg(feature = "yaml")]
+pub use crate::format::YamlRoomLoader;
+pub use crate::patch::{ApplyPatchError, ComponentChangeSet, PrefabPatch, PrefabsPatch, RoomPatch};
+pub use crate::progress::{RoomLoadProgress, RoomProgress};
+pub use crate::reflect::ReflectedComponent;
+pub use crate::schema::{FieldSchema, PrefabSchema, RoomValidationError};
+pub use crate::writer::{RoomWriter, WriteRoomError};
+pub use hana_prefab_derive::Prefab;
+
 /// The plugin that handles the loading and tracking of rooms and prefabs
 pub struct RoomPlugin;
 
@@ -18,78 +56,268 @@ impl Plugin for RoomPlugin {
         app.init_resource::<PrefabRegistry>();
         app.init_resource::<RoomTracker>();
         app.init_asset_loader::<RoomLoader>();
+        #[cfg(feature = "json")]
+        app.init_asset_loader::<JsonRoomLoader>();
+        #[cfg(feature = "toml")]
+        app.init_asset_loader::<TomlRoomLoader>();
+        #[cfg(feature = "yaml")]
+        app.init_asset_loader::<YamlRoomLoader>();
+        #[cfg(feature = "binary")]
+        app.init_asset_loader::<BinaryRoomLoader>();
+        app.add_event::<UnknownPrefabType>();
+        app.add_event::<InvalidRoom>();
+        app.add_event::<RoomSpawned>();
+        app.add_event::<RoomReloaded>();
+        app.add_event::<RoomDespawned>();
+        app.add_event::<PrefabSpawned>();
+        app.add_event::<PrefabUpdated>();
         app.add_systems(Update, room_system);
     }
 }
 
-/// A struct that contains an ammount of prefabs, each room is defined in a ron file
-#[derive(Asset, Deserialize, TypePath, Debug)]
+/// A struct that contains an ammount of prefabs, each room is defined in a ron file.
+///
+/// Prefabs and resources keep the order of the file and are spawned in that order,
+/// unless an entry declares an `order` or the siblings it is spawned `after`.
+#[derive(Asset, Serialize, Deserialize, TypePath, Debug, Clone)]
 pub struct Room {
-    prefabs: HashMap<String, PrefabData>,
+    /// Paths of rooms whose prefabs and resources are merged into this room
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) includes: Vec<String>,
+    pub(crate) prefabs: IndexMap<String, PrefabData>,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub(crate) resources: IndexMap<String, PrefabData>,
+    /// The settings the room was loaded with
+    #[serde(skip)]
+    pub(crate) settings: RoomLoaderSettings,
+    /// The assets referred to by asset path fields, loaded together with the room
+    #[serde(skip)]
+    #[dependency]
+    pub(crate) dependencies: Vec<UntypedHandle>,
+}
+
+impl Room {
+    /// Creates a room from its prefabs and resources, they are spawned in the order of the maps
+    pub fn new(
+        prefabs: IndexMap<String, PrefabData>,
+        resources: IndexMap<String, PrefabData>,
+    ) -> Self {
+        Self {
+            includes: Vec::new(),
+            prefabs,
+            resources,
+            settings: RoomLoaderSettings::default(),
+            dependencies: Vec::new(),
+        }
+    }
+
+    /// Reads a room file from the asset folder without the [AssetServer].
+    ///
+    /// The format is picked from the extension of the path, and includes, variants, the profile
+    /// and the order are resolved the same way the room loaders do. Assets referred to by asset path
+    /// fields are not loaded.
+    pub fn read(
+        assets: impl AsRef<Path>,
+        path: &str,
+        settings: &RoomLoaderSettings,
+        type_registry: &AppTypeRegistry,
+    ) -> Result<Room, LoadRoomError> {
+        let assets = assets.as_ref();
+        let bytes = std::fs::read(assets.join(path))?;
+        block_on(read_room(
+            path,
+            &bytes,
+            RoomFormat::from_path(path),
+            settings,
+            &mut FileIncludes(assets),
+            type_registry,
+        ))
+    }
+
+    /// Paths of the rooms whose prefabs and resources are merged into this room
+    pub fn includes(&self) -> &[String] {
+        &self.includes
+    }
+
+    /// The top level prefabs in the order they are spawned
+    pub fn prefabs(&self) -> &IndexMap<String, PrefabData> {
+        &self.prefabs
+    }
+
+    /// The top level prefabs, changes to a room asset are applied to its spawned instances
+    pub fn prefabs_mut(&mut self) -> &mut IndexMap<String, PrefabData> {
+        &mut self.prefabs
+    }
+
+    /// The resources in the order they are inserted
+    pub fn resources(&self) -> &IndexMap<String, PrefabData> {
+        &self.resources
+    }
+
+    /// The resources, changes to a room asset are applied to its spawned instances
+    pub fn resources_mut(&mut self) -> &mut IndexMap<String, PrefabData> {
+        &mut self.resources
+    }
+
+    /// The settings the room was loaded with
+    pub fn settings(&self) -> &RoomLoaderSettings {
+        &self.settings
+    }
 }
 
 /// A struct containing the data of a single prefab field
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct PrefabData {
-    #[serde(rename = "type")]
+    /// The type of the prefab, can be left out if the prefab extends a base entry
+    /// or only declares reflected components
+    #[serde(rename = "type", default, skip_serializing_if = "String::is_empty")]
     pub prefab_type: String,
+    #[serde(default, serialize_with = "serialize_sorted")]
     pub fields: HashMap<String, PrefabField>,
+    /// Components keyed by their type path, inserted through reflection after the prefab is spawned
+    #[serde(
+        default,
+        skip_deserializing,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_sorted"
+    )]
+    pub components: HashMap<String, ReflectedComponent>,
+    /// Prefabs that are spawned as children of this prefab
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub children: IndexMap<String, PrefabData>,
+    /// Overrides of the type, fields, components and children, keyed by the profile that selects them
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub profiles: IndexMap<String, PrefabData>,
+    /// The key of a base entry whose type, fields and children this prefab overrides
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    /// Templates are only used as base entries and are never spawned
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub template: bool,
+    /// Entries with a lower order are spawned before their siblings, entries without one use `0`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<i32>,
+    /// The keys of siblings that have to be spawned before this entry
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after: Vec<String>,
 }
 
-impl PrefabData {
-    fn get_changed_fields(
-        old_prefab: &PrefabData,
-        new_prefab: &PrefabData,
-    ) -> HashMap<String, PrefabField> {
-        if old_prefab.prefab_type != new_prefab.prefab_type {
-            warn!("trying to find changed fields of prefabs of different types (old_prefab: {}, new_prefab: {})", old_prefab.prefab_type, new_prefab.prefab_type);
-            return HashMap::new();
-        }
-
-        new_prefab
-            .fields
-            .iter()
-            .filter_map(|(key, field)| match old_prefab.fields.get(key) {
-                Some(other_field) => {
-                    if other_field != field {
-                        Some((key.clone(), field.clone()))
-                    } else {
-                        None
-                    }
-                }
-                None => Some((key.clone(), field.clone())),
-            })
-            .collect()
+/// The key used for a prefab, children are keyed by the path from the root of the room
+pub(crate) fn child_key(parent_key: Option<&str>, name: &str) -> String {
+    match parent_key {
+        Some(parent_key) => format!("{parent_key}/{name}"),
+        None => name.to_string(),
     }
 }
 
-/// An enum used for determining type of a field.
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
-#[serde(untagged)]
-pub enum PrefabField {
-    Number(f32),
-    Bool(bool),
-    Vec2(f32, f32),
-    String(String),
-}
-
 /// All prefabs that should be loaded from a room needs to imlpement the prefab trait.
 pub trait Prefab {
     /// The method that is called when a prefab is loaded for the first time and needs to be spawned into the world
     fn spawn_prfab(
         &self,
-        fields: &HashMap<String, PrefabField>,
+        fields: &PrefabFields,
         commands: EntityCommands,
         asset_server: &AssetServer,
     );
 
-    /// The method that is called when a prefab was changed in the ron file
+    /// The method that is called when a prefab was changed in the ron file.
+    /// The change set contains the fields that were added, modified and removed.
+    fn update_prfab(
+        &self,
+        changes: &PrefabChangeSet,
+        asset_server: &AssetServer,
+        commands: EntityCommands,
+    );
+
+    /// The method that is called when a room is saved, returns the current fields of the spawned entity.
+    /// Fields that are not returned keep the value they were loaded with.
+    fn extract(&self, _entity: EntityRef) -> HashMap<String, PrefabField> {
+        HashMap::new()
+    }
+
+    /// The fields the prefab expects, rooms are validated against it before they are spawned.
+    /// Prefabs without a schema are not validated.
+    fn schema(&self) -> Option<PrefabSchema> {
+        None
+    }
+}
+
+/// A prefab whose functions do not need a value of the prefab, implemented by `#[derive(Prefab)]`.
+///
+/// Register it with [PrefabRegistry::register_derived], so no value of the struct has to be built.
+pub trait DerivedPrefab: 'static {
+    /// Spawns the prefab, see [Prefab::spawn_prfab]
+    fn spawn(fields: &PrefabFields, commands: EntityCommands, asset_server: &AssetServer);
+
+    /// Updates a spawned prefab, see [Prefab::update_prfab]
+    fn update(changes: &PrefabChangeSet, asset_server: &AssetServer, commands: EntityCommands);
+
+    /// Reads the fields back from a spawned entity, see [Prefab::extract]
+    fn extract(_entity: EntityRef) -> HashMap<String, PrefabField> {
+        HashMap::new()
+    }
+
+    /// The fields of the prefab, see [Prefab::schema]
+    fn schema() -> PrefabSchema;
+}
+
+/// Registers a [DerivedPrefab] without a value of the prefab
+struct Derived<P>(PhantomData<fn() -> P>);
+
+impl<P: DerivedPrefab> Prefab for Derived<P> {
+    fn spawn_prfab(
+        &self,
+        fields: &PrefabFields,
+        commands: EntityCommands,
+        asset_server: &AssetServer,
+    ) {
+        P::spawn(fields, commands, asset_server);
+    }
+
     fn update_prfab(
         &self,
-        changed_fields: &HashMap<String, PrefabField>,
+        changes: &PrefabChangeSet,
         asset_server: &AssetServer,
         commands: EntityCommands,
+    ) {
+        P::update(changes, asset_server, commands);
+    }
+
+    fn extract(&self, entity: EntityRef) -> HashMap<String, PrefabField> {
+        P::extract(entity)
+    }
+
+    fn schema(&self) -> Option<PrefabSchema> {
+        Some(P::schema())
+    }
+}
+
+/// Resources that should be loaded from a room needs to implement the resource prefab trait.
+pub trait ResourcePrefab {
+    /// The method that is called when a room with the resource is loaded
+    fn insert_resource(
+        &self,
+        fields: &PrefabFields,
+        commands: &mut Commands,
+        asset_server: &AssetServer,
+    );
+
+    /// The method that is called when the resource was changed in the ron file
+    fn update_resource(
+        &self,
+        changes: &PrefabChangeSet,
+        commands: &mut Commands,
+        asset_server: &AssetServer,
     );
+
+    /// The method that is called when the resource is removed from the ron file or the room is unloaded
+    fn remove_resource(&self, commands: &mut Commands);
+
+    /// The fields the resource expects, rooms are validated against it before they are spawned.
+    /// Resources without a schema are not validated.
+    fn schema(&self) -> Option<PrefabSchema> {
+        None
+    }
 }
 
 #[non_exhaustive]
@@ -101,15 +329,125 @@ pub enum LoadRoomError {
     /// A [RON](ron) Error
     #[error("Could not parse RON: {0}")]
     RonSpannedError(#[from] ron::error::SpannedError),
+    /// A [JSON](serde_json) Error
+    #[cfg(feature = "json")]
+    #[error("Could not parse JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    /// A [TOML](toml) Error
+    #[cfg(feature = "toml")]
+    #[error("Could not parse TOML: {0}")]
+    Toml(#[from] toml::de::Error),
+    /// A [YAML](serde_yaml) Error
+    #[cfg(feature = "yaml")]
+    #[error("Could not parse YAML: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    /// A [binary room](crate::binary) Error
+    #[cfg(feature = "binary")]
+    #[error("Could not read binary room: {0}")]
+    Binary(#[from] crate::binary::BinaryRoomError),
+    /// An included room could not be read
+    #[error("Could not read included room: {0}")]
+    ReadInclude(#[from] ReadAssetBytesError),
+    /// An included room could not be loaded
+    #[error("Could not load included room {path}: {error}")]
+    Include {
+        path: String,
+        #[source]
+        error: Box<LoadRoomError>,
+    },
+    /// A room includes itself through its includes
+    #[error("Room include cycle: {0}")]
+    IncludeCycle(String),
+    /// A prefab extends an entry that does not exist
+    #[error("Prefab {key} extends the unknown entry {base}")]
+    UnknownBase { key: String, base: String },
+    /// A prefab extends itself through its bases
+    #[error("Prefab extends cycle: {0}")]
+    ExtendsCycle(String),
+    /// A prefab neither declares a type or components nor extends an entry with a type
+    #[error("Prefab {0} has no type")]
+    MissingType(String),
+    /// A prefab is spawned after a sibling that does not exist
+    #[error("Prefab {key} is spawned after the unknown sibling {after}")]
+    UnknownAfter { key: String, after: String },
+    /// Prefabs are spawned after each other in a cycle
+    #[error("Prefab spawn order cycle: {0}")]
+    OrderCycle(String),
+}
+
+/// Settings for a single room load, passed with [AssetServer::load_with_settings].
+///
+/// Bevy loads every asset path once, so the settings of the first load of a path are used.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomLoaderSettings {
+    /// Fail the room when a prefab has an unknown type or a field its schema does not declare,
+    /// instead of only logging a warning
+    pub strict: bool,
+    /// Prepended to the key of every prefab and every reference, so rooms can share keys
+    pub key_prefix: String,
+    /// The transform of a [RoomOffset] entity between the instance and the top level prefabs,
+    /// written as `(translation, rotation, scale)` arrays in meta files
+    #[serde(with = "offset_serde")]
+    pub offset: Transform,
+    /// The profile whose overrides are merged into the prefabs of the room
+    pub profile: Option<String>,
+    /// Delay spawning the room until all assets its asset path fields refer to are loaded
+    pub wait_for_dependencies: bool,
+}
+
+/// Serializes a [Transform] as plain arrays, the reflected form of components is kept unchanged
+mod offset_serde {
+    use bevy::prelude::*;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    type Arrays = ([f32; 3], [f32; 4], [f32; 3]);
+
+    pub(super) fn serialize<S: Serializer>(
+        transform: &Transform,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let arrays: Arrays = (
+            transform.translation.to_array(),
+            transform.rotation.to_array(),
+            transform.scale.to_array(),
+        );
+        arrays.serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Transform, D::Error> {
+        let (translation, rotation, scale) = Arrays::deserialize(deserializer)?;
+        Ok(Transform {
+            translation: Vec3::from_array(translation),
+            rotation: Quat::from_array(rotation),
+            scale: Vec3::from_array(scale),
+        })
+    }
 }
 
 /// The assetloader for the room asset
-#[derive(Default)]
-pub struct RoomLoader;
+pub struct RoomLoader {
+    type_registry: AppTypeRegistry,
+    asset_fields: Arc<RwLock<AssetFields>>,
+}
+
+impl FromWorld for RoomLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            type_registry: world.resource::<AppTypeRegistry>().clone(),
+            asset_fields: world
+                .get_resource_or_insert_with(PrefabRegistry::default)
+                .asset_fields
+                .clone(),
+        }
+    }
+}
 
 impl AssetLoader for RoomLoader {
     type Asset = Room;
-    type Settings = ();
+    type Settings = RoomLoaderSettings;
     type Error = LoadRoomError;
 
     fn extensions(&self) -> &[&str] {
@@ -119,158 +457,618 @@ impl AssetLoader for RoomLoader {
     fn load<'a>(
         &'a self,
         reader: &'a mut bevy::asset::io::Reader,
-        _settings: &'a Self::Settings,
+        settings: &'a Self::Settings,
         load_context: &'a mut bevy::asset::LoadContext,
     ) -> bevy::utils::BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
-        Box::pin(async move {
-            debug!("loading room: {:?}", load_context.path());
-            let mut bytes = Vec::new();
-            reader.read_to_end(&mut bytes).await?;
-            let room: Room = ron::de::from_bytes(&bytes)?;
-            Ok(room)
-        })
+        Box::pin(self.load_room(reader, RoomFormat::Ron, settings, load_context))
     }
 }
 
-/// Tracks which rooms are currently being loaded.
-#[derive(Resource, Default)]
-struct RoomTracker {
-    rooms: HashMap<AssetId<Room>, HashMap<String, (Entity, PrefabData)>>,
+impl RoomLoader {
+    /// Reads a room in the given format and resolves its includes, variants, profile and order,
+    /// shared by the loaders of all formats
+    pub(crate) async fn load_room(
+        &self,
+        reader: &mut bevy::asset::io::Reader<'_>,
+        format: RoomFormat,
+        settings: &RoomLoaderSettings,
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Room, LoadRoomError> {
+        debug!("loading room: {:?}", load_context.path());
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+
+        let path = load_context.path().to_string_lossy().into_owned();
+        let mut room = read_room(
+            &path,
+            &bytes,
+            format,
+            settings,
+            load_context,
+            &self.type_registry,
+        )
+        .await?;
+        room.dependencies = self
+            .asset_fields
+            .read()
+            .unwrap()
+            .load_dependencies(&room, load_context);
+
+        Ok(room)
+    }
 }
 
-/// Tracks rooms and whenever changes happens to a room
-fn room_system(
-    mut asset_events: EventReader<AssetEvent<Room>>,
-    mut commands: Commands,
-    registry: Res<PrefabRegistry>,
-    mut room_tracker: ResMut<RoomTracker>,
-    room_assets: Res<Assets<Room>>,
-    asset_server: Res<AssetServer>,
+/// Parses a room and resolves its includes, variants, profile and order.
+///
+/// This is everything the asset loaders do apart from loading dependencies,
+/// so tools can read rooms exactly the way they are loaded.
+pub(crate) async fn read_room(
+    path: &str,
+    bytes: &[u8],
+    format: RoomFormat,
+    settings: &RoomLoaderSettings,
+    includes: &mut impl IncludeReader,
+    type_registry: &AppTypeRegistry,
+) -> Result<Room, LoadRoomError> {
+    let mut room = format.parse(bytes, type_registry)?;
+
+    let mut include_stack = vec![include_path(path)];
+    resolve_includes(&mut room, includes, type_registry, &mut include_stack).await?;
+    resolve_variants(&mut room)?;
+    apply_profile(&mut room, settings.profile.as_deref());
+    sort_room(&mut room)?;
+
+    if !settings.key_prefix.is_empty() {
+        room.prefabs = prefix_keys(&settings.key_prefix, room.prefabs);
+    }
+    room.settings = settings.clone();
+
+    Ok(room)
+}
+
+/// Reads the files of included rooms
+pub(crate) trait IncludeReader: Send {
+    fn read_include<'a>(
+        &'a mut self,
+        path: &'a str,
+    ) -> BoxedFuture<'a, Result<Vec<u8>, LoadRoomError>>;
+}
+
+/// The asset loaders read includes through the [LoadContext], so changes to them reload the room
+impl IncludeReader for LoadContext<'_> {
+    fn read_include<'a>(
+        &'a mut self,
+        path: &'a str,
+    ) -> BoxedFuture<'a, Result<Vec<u8>, LoadRoomError>> {
+        Box::pin(async move { Ok(self.read_asset_bytes(path.to_string()).await?) })
+    }
+}
+
+/// Reads included rooms from the asset folder, used when rooms are read without the [AssetServer]
+pub(crate) struct FileIncludes<'a>(pub(crate) &'a Path);
+
+impl IncludeReader for FileIncludes<'_> {
+    fn read_include<'a>(
+        &'a mut self,
+        path: &'a str,
+    ) -> BoxedFuture<'a, Result<Vec<u8>, LoadRoomError>> {
+        Box::pin(async move { Ok(std::fs::read(self.0.join(path))?) })
+    }
+}
+
+/// Moves the reflected components that were read in a separate pass into their prefabs
+pub(crate) fn attach_components(
+    prefabs: &mut IndexMap<String, PrefabData>,
+    components: HashMap<String, ComponentTree>,
 ) {
-    for event in asset_events.read() {
-        match event {
-            AssetEvent::Added { id } => {
-                debug!("Room loaded parsing room. Room:{:?}", id);
-                let room = room_assets.get(*id).unwrap();
-
-                let entities = room
-                    .prefabs
-                    .iter()
-                    .map(|(id, prefab_data)| {
-                        let commands = commands.spawn_empty();
-                        let entity = commands.id();
-                        registry.spawn(prefab_data, commands, &asset_server);
-                        (id.clone(), (entity, prefab_data.clone()))
-                    })
-                    .collect();
+    for (name, tree) in components {
+        if let Some(prefab_data) = prefabs.get_mut(&name) {
+            prefab_data.components = tree.components;
+            attach_components(&mut prefab_data.children, tree.children);
+            attach_components(&mut prefab_data.profiles, tree.profiles);
+        }
+    }
+}
+
+/// Prepends the prefix to the keys of the top level prefabs and to every reference in the room,
+/// the keys of children follow from the keys of their parents
+fn prefix_keys(
+    prefix: &str,
+    prefabs: IndexMap<String, PrefabData>,
+) -> IndexMap<String, PrefabData> {
+    fn prefix_refs(prefix: &str, prefab_data: &mut PrefabData) {
+        for field in prefab_data.fields.values_mut() {
+            field.prefix_refs(prefix);
+        }
+        for child in prefab_data.children.values_mut() {
+            prefix_refs(prefix, child);
+        }
+    }
 
-                room_tracker.rooms.insert(*id, entities);
+    prefabs
+        .into_iter()
+        .map(|(name, mut prefab_data)| {
+            prefix_refs(prefix, &mut prefab_data);
+            for after in &mut prefab_data.after {
+                after.insert_str(0, prefix);
             }
-            AssetEvent::Modified { id } => {
-                debug!("Room modified, reparsing room. Room:{:?}", id);
+            (format!("{prefix}{name}"), prefab_data)
+        })
+        .collect()
+}
 
-                let room = room_assets.get(*id).unwrap();
+/// Merges the prefabs and resources of all included rooms into the room.
+///
+/// Includes are merged in order, so a later include overrides keys of an earlier one,
+/// and the prefabs and resources declared in the room itself override all included ones.
+/// Overridden entries keep the position of the entry they override.
+fn resolve_includes<'a>(
+    room: &'a mut Room,
+    includes: &'a mut impl IncludeReader,
+    type_registry: &'a AppTypeRegistry,
+    include_stack: &'a mut Vec<AssetPath<'static>>,
+) -> BoxedFuture<'a, Result<(), LoadRoomError>> {
+    Box::pin(async move {
+        let mut prefabs = IndexMap::new();
+        let mut resources = IndexMap::new();
 
-                let entities: HashMap<String, (Entity, PrefabData)> = room
-                    .prefabs
+        for include in &room.includes {
+            let include_path = include_path(include);
+            if include_stack.contains(&include_path) {
+                let cycle: Vec<String> = include_stack
                     .iter()
-                    .map(
-                        |(name, new_prefab)| match room_tracker.rooms[id].get(name) {
-                            Some((entity, old_prefab)) => {
-                                let changed_fields =
-                                    PrefabData::get_changed_fields(old_prefab, new_prefab);
-
-                                registry.update(
-                                    &new_prefab.prefab_type,
-                                    changed_fields,
-                                    commands.entity(entity.clone()),
-                                    &asset_server,
-                                );
-
-                                (name.clone(), (entity.clone(), new_prefab.clone()))
-                            }
-                            None => {
-                                let commands = commands.spawn_empty();
-                                let entity = commands.id();
-                                registry.spawn(new_prefab, commands, &asset_server);
-                                (name.clone(), (entity, new_prefab.clone()))
-                            }
-                        },
-                    )
+                    .chain([&include_path])
+                    .map(ToString::to_string)
                     .collect();
+                return Err(LoadRoomError::IncludeCycle(cycle.join(" -> ")));
+            }
 
-                let room_keys: HashSet<&String> = room_tracker.rooms[id].keys().collect();
-                let new_room_keys = entities.keys().collect();
+            debug!("loading included room: {:?}", include);
+            include_stack.push(include_path);
+            let included = load_include(include, includes, type_registry, include_stack).await;
+            include_stack.pop();
 
-                let diff = room_keys.difference(&new_room_keys);
+            let included = included.map_err(|error| match error {
+                LoadRoomError::IncludeCycle(_) => error,
+                error => LoadRoomError::Include {
+                    path: include.clone(),
+                    error: Box::new(error),
+                },
+            })?;
 
-                let remove_count = diff
-                    .map(|key| room_tracker.rooms[id][*key].0)
-                    .map(|entity| commands.entity(entity).despawn())
-                    .count();
+            prefabs.extend(included.prefabs);
+            resources.extend(included.resources);
+        }
 
-                debug!("Removed {} entities", remove_count);
+        prefabs.extend(room.prefabs.drain(..));
+        resources.extend(room.resources.drain(..));
+        room.prefabs = prefabs;
+        room.resources = resources;
 
-                drop(room_keys);
-                drop(new_room_keys);
+        Ok(())
+    })
+}
 
-                room_tracker.rooms.insert(*id, entities);
-            }
-            AssetEvent::Removed { id } => {
-                debug!("Room with handle {id:?} removed");
-                if let Some(entities) = room_tracker.rooms.remove(id) {
-                    for (_, (entity, _)) in entities {
-                        commands.entity(entity).despawn();
-                    }
-                }
-            }
-            AssetEvent::Unused { id } => {
-                debug!("Room with handle {id:?} unused");
-                if let Some(entities) = room_tracker.rooms.remove(id) {
-                    for (_, (entity, _)) in entities {
-                        commands.entity(entity).despawn();
-                    }
-                }
-            }
-            AssetEvent::LoadedWithDependencies { id: _ } => {}
+/// Normalizes the path of a room so the same file is found in an include cycle
+/// no matter how it is written, such as `rooms/./a.ron` or `rooms\a.ron` on Windows
+pub(crate) fn include_path(path: &str) -> AssetPath<'static> {
+    AssetPath::default()
+        .resolve(path)
+        .unwrap_or_else(|_| AssetPath::from_path(Path::new(path)).into_owned())
+}
+
+pub(crate) async fn load_include(
+    include: &str,
+    includes: &mut impl IncludeReader,
+    type_registry: &AppTypeRegistry,
+    include_stack: &mut Vec<AssetPath<'static>>,
+) -> Result<Room, LoadRoomError> {
+    let bytes = includes.read_include(include).await?;
+    let mut included = RoomFormat::from_path(include).parse(&bytes, type_registry)?;
+    resolve_includes(&mut included, includes, type_registry, include_stack).await?;
+    Ok(included)
+}
+
+/// Decides what happens when a room uses a prefab type that is not registered in the [PrefabRegistry]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownPrefabPolicy {
+    /// Skip the prefab and log a warning
+    #[default]
+    Skip,
+    /// Spawn a placeholder entity with a [MissingPrefab] component
+    Placeholder,
+    /// Do not spawn or update any prefab in the room
+    FailRoom,
+}
+
+/// A placeholder component for a prefab whose type is not registered
+#[derive(Component, Debug, Clone)]
+pub struct MissingPrefab {
+    pub type_name: String,
+    pub fields: HashMap<String, PrefabField>,
+}
+
+impl MissingPrefab {
+    pub(crate) fn new(prefab_data: &PrefabData) -> Self {
+        Self {
+            type_name: prefab_data.prefab_type.clone(),
+            fields: prefab_data.fields.clone(),
         }
     }
 }
 
+/// Marks the child of a room instance that holds the offset of the room, the top level prefabs
+/// of a room loaded with an offset are spawned as its children
+#[derive(Component, Debug, Clone, Copy)]
+pub struct RoomOffset;
+
+/// An event that is sent whenever a room uses a prefab type that is not registered
+#[derive(Event, Debug, Clone)]
+pub struct UnknownPrefabType {
+    /// The room containing the prefab
+    pub room: AssetId<Room>,
+    /// The entity holding the handle of the room instance
+    pub instance: Entity,
+    /// The key of the prefab in the room
+    pub key: String,
+    /// The unknown type of the prefab
+    pub type_name: String,
+}
+
+/// An event that is sent when a loaded or reloaded room does not match the schemas of its prefabs.
+/// The room is not spawned, instances of a reloaded room keep their previous state.
+#[derive(Event, Debug, Clone)]
+pub struct InvalidRoom {
+    /// The invalid room
+    pub room: AssetId<Room>,
+    /// The path of the room and every violation found in it
+    pub error: RoomValidationError,
+}
+
+/// An event that is sent once all prefabs of a room instance were spawned
+#[derive(Event, Debug, Clone)]
+pub struct RoomSpawned {
+    /// The room that was spawned
+    pub room: AssetId<Room>,
+    /// The entity holding the handle of the room instance
+    pub instance: Entity,
+    /// The spawned entities keyed by prefab key, children use `parent/child` keys
+    pub entities: HashMap<String, Entity>,
+}
+
+/// An event that is sent after a room instance was updated because its room was reloaded.
+///
+/// A prefab whose type changed is respawned and reported as both removed and added.
+#[derive(Event, Debug, Clone)]
+pub struct RoomReloaded {
+    /// The room that was reloaded
+    pub room: AssetId<Room>,
+    /// The entity holding the handle of the room instance
+    pub instance: Entity,
+    /// The prefabs that were spawned by the reload, keyed by prefab key
+    pub added: HashMap<String, Entity>,
+    /// The keys of the prefabs whose fields changed
+    pub updated: Vec<String>,
+    /// The keys of the prefabs that were despawned by the reload
+    pub removed: Vec<String>,
+}
+
+/// An event that is sent after the prefabs and resources of a room instance were despawned
+#[derive(Event, Debug, Clone)]
+pub struct RoomDespawned {
+    /// The room of the despawned instance
+    pub room: AssetId<Room>,
+    /// The entity that held the handle of the room instance, it may no longer exist
+    pub instance: Entity,
+}
+
+/// An event that is sent for every prefab spawned from a room, including placeholders
+#[derive(Event, Debug, Clone)]
+pub struct PrefabSpawned {
+    /// The entity holding the handle of the room instance
+    pub instance: Entity,
+    /// The key of the prefab in the room
+    pub key: String,
+    /// The type of the prefab
+    pub prefab_type: String,
+    /// The spawned entity
+    pub entity: Entity,
+}
+
+/// An event that is sent for every prefab whose fields or components changed when its room was reloaded
+#[derive(Event, Debug, Clone)]
+pub struct PrefabUpdated {
+    /// The entity holding the handle of the room instance
+    pub instance: Entity,
+    /// The type of the prefab
+    pub prefab_type: String,
+    /// The updated entity
+    pub entity: Entity,
+    /// The changes made to the fields of the prefab, also holds its key
+    pub changes: PrefabChangeSet,
+}
+
 /// A struct that tracks the spawn functions for all available prefabs
 #[derive(Default, Resource)]
 pub struct PrefabRegistry {
     prefabs: HashMap<String, Box<dyn Prefab + Sync + Send>>,
+    resources: HashMap<String, Box<dyn ResourcePrefab + Sync + Send>>,
+    prefab_schemas: HashMap<String, PrefabSchema>,
+    resource_schemas: HashMap<String, PrefabSchema>,
+    /// Shared with the [RoomLoader], which loads the assets of these fields as room dependencies
+    asset_fields: Arc<RwLock<AssetFields>>,
+    unknown_prefab_policy: UnknownPrefabPolicy,
 }
 
 impl PrefabRegistry {
     /// Register a prefab to the registry, all prefabs that are going to be loaded needs to be registered before loading.
     pub fn register_prefab(&mut self, name: &str, prefab: impl Prefab + Sync + Send + 'static) {
+        let schema = prefab.schema();
+        let asset_fields = schema.iter().flat_map(PrefabSchema::asset_fields).collect();
+        self.asset_fields
+            .write()
+            .unwrap()
+            .prefabs
+            .insert(name.to_string(), asset_fields);
+        match schema {
+            Some(schema) => self.prefab_schemas.insert(name.to_string(), schema),
+            None => self.prefab_schemas.remove(name),
+        };
         self.prefabs.insert(name.to_string(), Box::new(prefab));
     }
 
+    /// Register a prefab that derives [Prefab](hana_prefab_derive::Prefab) by its type,
+    /// all prefabs that are going to be loaded needs to be registered before loading.
+    pub fn register_derived<P: DerivedPrefab>(&mut self, name: &str) {
+        self.register_prefab(name, Derived::<P>(PhantomData));
+    }
+
+    /// Register a resource prefab to the registry, all resources that are going to be loaded needs to be registered before loading.
+    pub fn register_resource(
+        &mut self,
+        name: &str,
+        resource: impl ResourcePrefab + Sync + Send + 'static,
+    ) {
+        let schema = resource.schema();
+        let asset_fields = schema.iter().flat_map(PrefabSchema::asset_fields).collect();
+        self.asset_fields
+            .write()
+            .unwrap()
+            .resources
+            .insert(name.to_string(), asset_fields);
+        match schema {
+            Some(schema) => self.resource_schemas.insert(name.to_string(), schema),
+            None => self.resource_schemas.remove(name),
+        };
+        self.resources.insert(name.to_string(), Box::new(resource));
+    }
+
+    /// Returns true if a prefab of the given type is registered
+    pub fn contains(&self, prefab_type: &str) -> bool {
+        self.prefabs.contains_key(prefab_type)
+    }
+
+    /// The schema of the prefab type, if the prefab declares one
+    pub fn prefab_schema(&self, prefab_type: &str) -> Option<&PrefabSchema> {
+        self.prefab_schemas.get(prefab_type)
+    }
+
+    /// The schema of the resource type, if the resource declares one
+    pub fn resource_schema(&self, resource_type: &str) -> Option<&PrefabSchema> {
+        self.resource_schemas.get(resource_type)
+    }
+
+    /// The names of all registered prefab types
+    pub fn prefab_types(&self) -> impl Iterator<Item = &str> {
+        self.prefabs.keys().map(String::as_str)
+    }
+
+    /// The names of all registered resource types
+    pub fn resource_types(&self) -> impl Iterator<Item = &str> {
+        self.resources.keys().map(String::as_str)
+    }
+
+    /// A JSON Schema of room files using the registered prefab and resource types,
+    /// editors can use it to complete type names and field keys
+    pub fn json_schema(&self) -> serde_json::Value {
+        room_json_schema(self)
+    }
+
+    /// Validates all prefabs and resources of a room against the schemas of their types
+    /// and returns every violation, prefabs with an unknown type or without a schema are not checked
+    pub fn validate_room(&self, room: &Room) -> Vec<PrefabFieldError> {
+        validate_room(room, self)
+    }
+
+    /// The policy used when a room contains a prefab type that is not registered
+    pub fn unknown_prefab_policy(&self) -> UnknownPrefabPolicy {
+        self.unknown_prefab_policy
+    }
+
+    /// Sets the policy used when a room contains a prefab type that is not registered
+    pub fn set_unknown_prefab_policy(&mut self, policy: UnknownPrefabPolicy) {
+        self.unknown_prefab_policy = policy;
+    }
+
     /// Calls the correct spawn function for a prefab of given type
     pub fn spawn(
         &self,
+        key: &str,
         prefab_data: &PrefabData,
         commands: EntityCommands,
         asset_server: &AssetServer,
     ) {
-        self.prefabs[&prefab_data.prefab_type].spawn_prfab(
-            &prefab_data.fields,
-            commands,
-            asset_server,
-        )
+        match self.prefabs.get(&prefab_data.prefab_type) {
+            Some(prefab) => prefab.spawn_prfab(
+                &PrefabFields::new(key, &prefab_data.fields),
+                commands,
+                asset_server,
+            ),
+            None => warn!(
+                "No prefab registered with the type {}",
+                prefab_data.prefab_type
+            ),
+        }
+    }
+
+    /// Calls the correct extract function for a prefab of given type
+    pub fn extract(&self, prefab_type: &str, entity: EntityRef) -> HashMap<String, PrefabField> {
+        match self.prefabs.get(prefab_type) {
+            Some(prefab) => prefab.extract(entity),
+            None => HashMap::new(),
+        }
     }
 
     /// Calls the correct update function prefab
     pub fn update(
         &self,
         prefab_type: &String,
-        changed_fields: HashMap<String, PrefabField>,
+        changes: &PrefabChangeSet,
         commands: EntityCommands,
         asset_server: &AssetServer,
     ) {
-        self.prefabs[prefab_type].update_prfab(&changed_fields, asset_server, commands);
+        match self.prefabs.get(prefab_type) {
+            Some(prefab) => prefab.update_prfab(changes, asset_server, commands),
+            None => warn!("No prefab registered with the type {}", prefab_type),
+        }
+    }
+
+    /// Calls the correct insert function for a resource of given type.
+    /// Returns false if no resource of the type is registered.
+    pub fn insert_resource(
+        &self,
+        key: &str,
+        resource_data: &PrefabData,
+        commands: &mut Commands,
+        asset_server: &AssetServer,
+    ) -> bool {
+        match self.resources.get(&resource_data.prefab_type) {
+            Some(resource) => {
+                resource.insert_resource(
+                    &PrefabFields::new(key, &resource_data.fields),
+                    commands,
+                    asset_server,
+                );
+                true
+            }
+            None => {
+                warn!(
+                    "No resource registered with the type {}, skipping resource {}",
+                    resource_data.prefab_type, key
+                );
+                false
+            }
+        }
+    }
+
+    /// Calls the correct update function for a resource
+    pub fn update_resource(
+        &self,
+        resource_type: &String,
+        changes: &PrefabChangeSet,
+        commands: &mut Commands,
+        asset_server: &AssetServer,
+    ) {
+        match self.resources.get(resource_type) {
+            Some(resource) => resource.update_resource(changes, commands, asset_server),
+            None => warn!("No resource registered with the type {}", resource_type),
+        }
+    }
+
+    /// Calls the correct remove function for a resource
+    pub fn remove_resource(&self, resource_type: &String, commands: &mut Commands) {
+        match self.resources.get(resource_type) {
+            Some(resource) => resource.remove_resource(commands),
+            None => warn!("No resource registered with the type {}", resource_type),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use bevy::tasks::block_on;
+
+    use super::*;
+
+    /// Reads includes from memory instead of the asset folder
+    struct MemoryIncludes(HashMap<String, String>);
+
+    impl IncludeReader for MemoryIncludes {
+        fn read_include<'a>(
+            &'a mut self,
+            path: &'a str,
+        ) -> BoxedFuture<'a, Result<Vec<u8>, LoadRoomError>> {
+            let bytes = self.0.get(path).map(|room| room.as_bytes().to_vec());
+            Box::pin(async move { Ok(bytes.ok_or(std::io::Error::from(ErrorKind::NotFound))?) })
+        }
+    }
+
+    fn read(path: &str, files: &[(&str, String)]) -> Result<Room, LoadRoomError> {
+        let mut includes = MemoryIncludes(
+            files
+                .iter()
+                .map(|(path, room)| (path.to_string(), room.clone()))
+                .collect(),
+        );
+        let bytes = includes.0[path].as_bytes().to_vec();
+        block_on(read_room(
+            path,
+            &bytes,
+            RoomFormat::Ron,
+            &RoomLoaderSettings::default(),
+            &mut includes,
+            &AppTypeRegistry::default(),
+        ))
+    }
+
+    fn room(includes: &[&str], prefab: &str) -> String {
+        format!(
+            r#"(includes: {includes:?}, prefabs: {{ "{prefab}": (type: "Test", fields: {{}}) }})"#
+        )
+    }
+
+    #[test]
+    fn include_cycle_is_detected() {
+        let files = [
+            ("rooms/a.ron", room(&["rooms/b.ron"], "a")),
+            ("rooms/b.ron", room(&["rooms/a.ron"], "b")),
+        ];
+
+        match read("rooms/a.ron", &files) {
+            Err(LoadRoomError::IncludeCycle(cycle)) => {
+                assert_eq!(cycle, "rooms/a.ron -> rooms/b.ron -> rooms/a.ron")
+            }
+            result => panic!("expected an include cycle, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn include_cycle_compares_normalized_paths() {
+        let files = [
+            ("rooms/a.ron", room(&["rooms/b.ron"], "a")),
+            ("rooms/b.ron", room(&["rooms/./c/../a.ron"], "b")),
+            ("rooms/./c/../a.ron", room(&["rooms/b.ron"], "a")),
+        ];
+
+        assert!(matches!(
+            read("rooms/a.ron", &files),
+            Err(LoadRoomError::IncludeCycle(_))
+        ));
+    }
+
+    #[test]
+    fn repeated_includes_are_not_a_cycle() {
+        let files = [
+            ("rooms/a.ron", room(&["rooms/b.ron", "rooms/c.ron"], "a")),
+            ("rooms/b.ron", room(&["rooms/d.ron"], "b")),
+            ("rooms/c.ron", room(&["rooms/d.ron"], "c")),
+            ("rooms/d.ron", room(&[], "d")),
+        ];
+
+        let room = read("rooms/a.ron", &files).unwrap();
+        let keys: Vec<&str> = room.prefabs.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["d", "b", "c", "a"]);
     }
 }
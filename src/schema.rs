@@ -0,0 +1,453 @@
+use std::fmt::Write;
+
+use bevy::{
+    asset::{Asset, LoadContext, UntypedHandle},
+    utils::HashMap,
+};
+use indexmap::IndexMap;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::field::{FromPrefabField, PrefabField, PrefabFieldError};
+use crate::room::{child_key, PrefabData, PrefabRegistry, Room};
+
+/// The fields a prefab or resource expects in a room, used to validate rooms before they are spawned.
+///
+/// ```ignore
+/// PrefabSchema::new()
+///     .required::<String>("sprite")
+///     .optional::<Vec2>("position")
+///     .with_default::<f32>("speed", PrefabField::Number(300.0))
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PrefabSchema {
+    fields: Vec<FieldSchema>,
+}
+
+/// Starts loading the asset at a path as a dependency of the room that is being loaded
+pub(crate) type LoadAssetFn = fn(&mut LoadContext, &str) -> UntypedHandle;
+
+/// A single field declared in a [PrefabSchema]
+#[derive(Debug, Clone)]
+pub struct FieldSchema {
+    name: String,
+    expected: &'static str,
+    required: bool,
+    default: Option<PrefabField>,
+    matches: fn(&PrefabField) -> bool,
+    json_schema: fn() -> Value,
+    load_asset: Option<LoadAssetFn>,
+}
+
+impl FieldSchema {
+    fn new<T: FromPrefabField>(name: &str, required: bool, default: Option<PrefabField>) -> Self {
+        Self {
+            name: name.to_string(),
+            expected: T::EXPECTED,
+            required,
+            default,
+            matches: T::matches,
+            json_schema: T::json_schema,
+            load_asset: None,
+        }
+    }
+
+    fn asset<A: Asset>(name: &str, required: bool) -> Self {
+        Self {
+            load_asset: Some(|load_context, path| {
+                load_context.load::<A>(path.to_string()).untyped()
+            }),
+            ..Self::new::<String>(name, required, None)
+        }
+    }
+
+    /// The name of the field in the room
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name of the expected type, the same as [FromPrefabField::EXPECTED]
+    pub fn expected(&self) -> &'static str {
+        self.expected
+    }
+
+    /// Returns true if the room has to declare the field
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+
+    /// The value used when the field is not declared in the room
+    pub fn default(&self) -> Option<&PrefabField> {
+        self.default.as_ref()
+    }
+
+    /// Returns true if the field is the path of an asset that is loaded together with the room
+    pub fn is_asset(&self) -> bool {
+        self.load_asset.is_some()
+    }
+
+    /// Returns true if the field can be read as the expected type
+    pub fn matches(&self, field: &PrefabField) -> bool {
+        (self.matches)(field)
+    }
+
+    /// A JSON Schema describing the values the field accepts
+    pub fn json_schema(&self) -> Value {
+        let mut schema = (self.json_schema)();
+        if let (Value::Object(schema), Some(default)) = (&mut schema, &self.default) {
+            if let Ok(default) = serde_json::to_value(default) {
+                schema.insert("default".to_string(), default);
+            }
+        }
+        schema
+    }
+}
+
+impl PrefabSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares a field that has to be declared in the room
+    pub fn required<T: FromPrefabField>(mut self, name: &str) -> Self {
+        self.fields.push(FieldSchema::new::<T>(name, true, None));
+        self
+    }
+
+    /// Declares a field that can be left out of the room
+    pub fn optional<T: FromPrefabField>(mut self, name: &str) -> Self {
+        self.fields.push(FieldSchema::new::<T>(name, false, None));
+        self
+    }
+
+    /// Declares a field that is set to `default` when it is left out of the room
+    pub fn with_default<T: FromPrefabField>(mut self, name: &str, default: PrefabField) -> Self {
+        self.fields
+            .push(FieldSchema::new::<T>(name, false, Some(default)));
+        self
+    }
+
+    /// Declares a required asset path, the asset is loaded as a dependency of the room
+    pub fn required_asset<A: Asset>(mut self, name: &str) -> Self {
+        self.fields.push(FieldSchema::asset::<A>(name, true));
+        self
+    }
+
+    /// Declares an optional asset path, the asset is loaded as a dependency of the room
+    pub fn optional_asset<A: Asset>(mut self, name: &str) -> Self {
+        self.fields.push(FieldSchema::asset::<A>(name, false));
+        self
+    }
+
+    /// The declared fields in the order they were declared
+    pub fn fields(&self) -> &[FieldSchema] {
+        &self.fields
+    }
+
+    /// The declared field with the given name
+    pub fn field(&self, name: &str) -> Option<&FieldSchema> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Checks the fields of the prefab with the given key and adds every violation to `violations`
+    pub fn validate(
+        &self,
+        key: &str,
+        fields: &HashMap<String, PrefabField>,
+        violations: &mut Vec<PrefabFieldError>,
+    ) {
+        for schema in &self.fields {
+            match fields.get(&schema.name) {
+                Some(field) if !schema.matches(field) => {
+                    violations.push(PrefabFieldError::WrongType {
+                        prefab_key: key.to_string(),
+                        field: schema.name.clone(),
+                        expected: schema.expected,
+                        actual: field.variant_name(),
+                    })
+                }
+                None if schema.required => violations.push(PrefabFieldError::Missing {
+                    prefab_key: key.to_string(),
+                    field: schema.name.clone(),
+                    expected: schema.expected,
+                }),
+                _ => {}
+            }
+        }
+
+        let mut unknown: Vec<&String> = fields
+            .keys()
+            .filter(|name| self.field(name).is_none())
+            .collect();
+        unknown.sort();
+
+        violations.extend(unknown.into_iter().map(|name| PrefabFieldError::Unknown {
+            prefab_key: key.to_string(),
+            field: name.clone(),
+        }));
+    }
+
+    /// A JSON Schema of the `fields` map of a room entry
+    pub fn json_schema(&self) -> Value {
+        let properties: serde_json::Map<String, Value> = self
+            .fields
+            .iter()
+            .map(|field| (field.name.clone(), field.json_schema()))
+            .collect();
+        let required: Vec<&str> = self
+            .fields
+            .iter()
+            .filter(|field| field.required)
+            .map(|field| field.name.as_str())
+            .collect();
+
+        json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        })
+    }
+
+    /// The names and load functions of the asset path fields
+    pub(crate) fn asset_fields(&self) -> Vec<(String, LoadAssetFn)> {
+        self.fields
+            .iter()
+            .filter_map(|field| Some((field.name.clone(), field.load_asset?)))
+            .collect()
+    }
+
+    /// Inserts the default value of every field that is not declared
+    pub(crate) fn apply_defaults(&self, fields: &mut HashMap<String, PrefabField>) {
+        for schema in &self.fields {
+            if let Some(default) = &schema.default {
+                fields
+                    .entry(schema.name.clone())
+                    .or_insert_with(|| default.clone());
+            }
+        }
+    }
+}
+
+/// An error listing every field of a room that does not match the schema of its prefab
+#[derive(Debug, Error, Clone, PartialEq)]
+#[error("Room {path} does not match its prefab schemas:{}", list_violations(.violations))]
+pub struct RoomValidationError {
+    /// The asset path of the room
+    pub path: String,
+    /// All violations in the room, ordered by prefab key
+    pub violations: Vec<PrefabFieldError>,
+}
+
+fn list_violations(violations: &[PrefabFieldError]) -> String {
+    violations
+        .iter()
+        .fold(String::new(), |mut list, violation| {
+            let _ = write!(list, "\n  {violation}");
+            list
+        })
+}
+
+/// Validates all prefabs, including children, and resources of a room
+/// against the schemas of their registered types
+pub(crate) fn validate_room(room: &Room, registry: &PrefabRegistry) -> Vec<PrefabFieldError> {
+    let mut violations = Vec::new();
+    validate_prefabs(None, &room.prefabs, registry, &mut violations);
+
+    for (key, resource_data) in sorted(&room.resources) {
+        if let Some(schema) = registry.resource_schema(&resource_data.prefab_type) {
+            schema.validate(key, &resource_data.fields, &mut violations);
+        }
+    }
+
+    violations
+}
+
+fn validate_prefabs(
+    parent_key: Option<&str>,
+    prefabs: &IndexMap<String, PrefabData>,
+    registry: &PrefabRegistry,
+    violations: &mut Vec<PrefabFieldError>,
+) {
+    for (name, prefab_data) in sorted(prefabs) {
+        let key = child_key(parent_key, name);
+
+        if let Some(schema) = registry.prefab_schema(&prefab_data.prefab_type) {
+            schema.validate(&key, &prefab_data.fields, violations);
+        }
+
+        validate_prefabs(Some(&key), &prefab_data.children, registry, violations);
+    }
+}
+
+/// A JSON Schema of a room file, listing the registered prefab and resource types
+/// and the fields of every type that declares a schema
+pub(crate) fn room_json_schema(registry: &PrefabRegistry) -> Value {
+    let mut prefab_types: Vec<&str> = registry.prefab_types().collect();
+    prefab_types.sort();
+    let mut resource_types: Vec<&str> = registry.resource_types().collect();
+    resource_types.sort();
+
+    let prefab_fields = typed_fields(&prefab_types, |prefab_type| {
+        registry.prefab_schema(prefab_type)
+    });
+    let resource_fields = typed_fields(&resource_types, |resource_type| {
+        registry.resource_schema(resource_type)
+    });
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "Room",
+        "type": "object",
+        "properties": {
+            "includes": { "type": "array", "items": { "type": "string" } },
+            "prefabs": {
+                "type": "object",
+                "additionalProperties": { "$ref": "#/definitions/prefab" },
+            },
+            "resources": {
+                "type": "object",
+                "additionalProperties": { "$ref": "#/definitions/resource" },
+            },
+        },
+        "required": ["prefabs"],
+        "definitions": {
+            "prefab": {
+                "type": "object",
+                "properties": {
+                    "type": { "enum": prefab_types },
+                    "fields": { "type": "object" },
+                    "components": { "type": "object" },
+                    "children": {
+                        "type": "object",
+                        "additionalProperties": { "$ref": "#/definitions/prefab" },
+                    },
+                    "profiles": {
+                        "type": "object",
+                        "additionalProperties": { "$ref": "#/definitions/profile" },
+                    },
+                    "extends": { "type": "string" },
+                    "template": { "type": "boolean" },
+                    "order": { "type": "integer" },
+                    "after": { "type": "array", "items": { "type": "string" } },
+                },
+                "additionalProperties": false,
+                "allOf": prefab_fields,
+            },
+            "resource": {
+                "type": "object",
+                "properties": {
+                    "type": { "enum": resource_types },
+                    "fields": { "type": "object" },
+                    "profiles": {
+                        "type": "object",
+                        "additionalProperties": { "$ref": "#/definitions/profile" },
+                    },
+                    "extends": { "type": "string" },
+                    "template": { "type": "boolean" },
+                    "order": { "type": "integer" },
+                    "after": { "type": "array", "items": { "type": "string" } },
+                },
+                "additionalProperties": false,
+                "allOf": resource_fields,
+            },
+            "profile": {
+                "type": "object",
+                "properties": {
+                    "type": { "type": "string" },
+                    "fields": { "type": "object" },
+                    "components": { "type": "object" },
+                    "children": {
+                        "type": "object",
+                        "additionalProperties": { "$ref": "#/definitions/prefab" },
+                    },
+                },
+                "additionalProperties": false,
+            },
+        },
+    })
+}
+
+/// Conditional schemas that check the `fields` of an entry against the schema of its `type`
+fn typed_fields<'a>(
+    types: &[&'a str],
+    schema: impl Fn(&'a str) -> Option<&'a PrefabSchema>,
+) -> Vec<Value> {
+    types
+        .iter()
+        .filter_map(|name| {
+            let schema = schema(name)?;
+            Some(json!({
+                "if": {
+                    "properties": { "type": { "const": name } },
+                    "required": ["type"],
+                },
+                "then": {
+                    "properties": { "fields": schema.json_schema() },
+                },
+            }))
+        })
+        .collect()
+}
+
+/// The asset path fields of every registered type, shared between the registry and the room loader
+#[derive(Default)]
+pub(crate) struct AssetFields {
+    pub(crate) prefabs: HashMap<String, Vec<(String, LoadAssetFn)>>,
+    pub(crate) resources: HashMap<String, Vec<(String, LoadAssetFn)>>,
+}
+
+impl AssetFields {
+    /// Starts loading every asset a prefab or resource of the room refers to
+    /// and returns the handles, so they become dependencies of the room
+    pub(crate) fn load_dependencies(
+        &self,
+        room: &Room,
+        load_context: &mut LoadContext,
+    ) -> Vec<UntypedHandle> {
+        let mut handles = Vec::new();
+        self.load_prefabs(&room.prefabs, load_context, &mut handles);
+
+        for resource_data in room.resources.values() {
+            if let Some(fields) = self.resources.get(&resource_data.prefab_type) {
+                load_assets(fields, resource_data, load_context, &mut handles);
+            }
+        }
+
+        handles
+    }
+
+    fn load_prefabs(
+        &self,
+        prefabs: &IndexMap<String, PrefabData>,
+        load_context: &mut LoadContext,
+        handles: &mut Vec<UntypedHandle>,
+    ) {
+        for prefab_data in prefabs.values() {
+            if let Some(fields) = self.prefabs.get(&prefab_data.prefab_type) {
+                load_assets(fields, prefab_data, load_context, handles);
+            }
+            self.load_prefabs(&prefab_data.children, load_context, handles);
+        }
+    }
+}
+
+fn load_assets(
+    asset_fields: &[(String, LoadAssetFn)],
+    prefab_data: &PrefabData,
+    load_context: &mut LoadContext,
+    handles: &mut Vec<UntypedHandle>,
+) {
+    for (name, load_asset) in asset_fields {
+        if let Some(PrefabField::String(path)) = prefab_data.fields.get(name) {
+            handles.push(load_asset(load_context, path));
+        }
+    }
+}
+
+/// The entries of a map ordered by key, so violations are reported in a stable order
+fn sorted(prefabs: &IndexMap<String, PrefabData>) -> Vec<(&String, &PrefabData)> {
+    let mut prefabs: Vec<_> = prefabs.iter().collect();
+    prefabs.sort_by_key(|(key, _)| *key);
+    prefabs
+}
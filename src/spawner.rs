@@ -0,0 +1,978 @@
+use bevy::{
+    asset::RecursiveDependencyLoadState, ecs::system::SystemParam, prelude::*, utils::HashMap,
+};
+use indexmap::IndexMap;
+
+use crate::patch::{diff_prefabs, ComponentChangeSet, PrefabPatch, PrefabsPatch};
+use crate::reflect::{insert_components, patch_components};
+use crate::room::{
+    child_key, InvalidRoom, MissingPrefab, PrefabChangeSet, PrefabData, PrefabFieldError,
+    PrefabRegistry, PrefabSpawned, PrefabUpdated, Room, RoomDespawned, RoomOffset, RoomReloaded,
+    RoomSpawned, RoomValidationError, UnknownPrefabPolicy, UnknownPrefabType,
+};
+
+/// Tracks the room instances that are currently spawned, keyed by the entity holding the room handle.
+#[derive(Resource, Default)]
+pub(crate) struct RoomTracker {
+    pub(crate) instances: HashMap<Entity, TrackedRoom>,
+    /// The resources of every room asset with spawned instances
+    pub(crate) resources: HashMap<AssetId<Room>, SharedResources>,
+    /// Whether a loaded room matches the schemas of its prefabs, each room is only validated once per load
+    validated: HashMap<AssetId<Room>, bool>,
+}
+
+impl RoomTracker {
+    /// Validates the room the first time it is seen and reports all violations,
+    /// returns false if the room should not be spawned
+    fn is_valid(
+        &mut self,
+        id: AssetId<Room>,
+        room: &Room,
+        registry: &PrefabRegistry,
+        asset_server: &AssetServer,
+        invalid_events: &mut EventWriter<InvalidRoom>,
+    ) -> bool {
+        *self.validated.entry(id).or_insert_with(|| {
+            let mut violations = registry.validate_room(room);
+
+            // Undeclared fields only fail rooms that were loaded as strict
+            if !room.settings.strict {
+                violations.retain(|violation| match violation {
+                    PrefabFieldError::Unknown { .. } => {
+                        warn!("{violation}");
+                        false
+                    }
+                    _ => true,
+                });
+            }
+
+            if violations.is_empty() {
+                return true;
+            }
+
+            let error = RoomValidationError {
+                path: asset_server
+                    .get_path(id)
+                    .map_or_else(|| format!("{id:?}"), |path| path.to_string()),
+                violations,
+            };
+            error!("{error}");
+            invalid_events.send(InvalidRoom { room: id, error });
+            false
+        })
+    }
+}
+
+/// The prefabs that were spawned from a single room instance
+pub(crate) struct TrackedRoom {
+    pub(crate) room: AssetId<Room>,
+    /// The parent of the top level prefabs, the instance or its [RoomOffset] child
+    root: Entity,
+    pub(crate) prefabs: IndexMap<String, TrackedPrefab>,
+    /// Whether the instance is counted in the [SharedResources] of its room,
+    /// instances of invalid rooms are tracked without spawning anything
+    holds_resources: bool,
+}
+
+impl TrackedRoom {
+    fn empty(room: AssetId<Room>, instance: Entity) -> Self {
+        Self {
+            room,
+            root: instance,
+            prefabs: IndexMap::new(),
+            holds_resources: false,
+        }
+    }
+}
+
+/// The resources inserted for a room asset. Resources are global, so they are shared by all
+/// instances of the room: inserted when the first instance is spawned and removed when the last
+/// instance is despawned.
+pub(crate) struct SharedResources {
+    /// The number of spawned instances of the room
+    instances: usize,
+    pub(crate) resources: IndexMap<String, PrefabData>,
+}
+
+/// A prefab that was spawned from a room together with its spawned children
+pub(crate) struct TrackedPrefab {
+    pub(crate) entity: Entity,
+    pub(crate) data: PrefabData,
+    pub(crate) children: IndexMap<String, TrackedPrefab>,
+}
+
+/// The writers for all events sent by the [room_system]
+#[derive(SystemParam)]
+pub(crate) struct RoomEvents<'w> {
+    unknown_prefab: EventWriter<'w, UnknownPrefabType>,
+    invalid_room: EventWriter<'w, InvalidRoom>,
+    room_spawned: EventWriter<'w, RoomSpawned>,
+    room_reloaded: EventWriter<'w, RoomReloaded>,
+    room_despawned: EventWriter<'w, RoomDespawned>,
+    prefab_spawned: EventWriter<'w, PrefabSpawned>,
+    prefab_updated: EventWriter<'w, PrefabUpdated>,
+}
+
+/// Spawns a room instance for every entity holding a loaded room handle
+/// and tracks whenever changes happens to a room
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn room_system(
+    mut asset_events: EventReader<AssetEvent<Room>>,
+    mut events: RoomEvents,
+    mut removed_handles: RemovedComponents<Handle<Room>>,
+    holders: Query<(Entity, &Handle<Room>, Has<Transform>)>,
+    mut commands: Commands,
+    registry: Res<PrefabRegistry>,
+    mut room_tracker: ResMut<RoomTracker>,
+    room_assets: Res<Assets<Room>>,
+    asset_server: Res<AssetServer>,
+) {
+    for instance in removed_handles.read() {
+        if holders.contains(instance) {
+            continue;
+        }
+
+        if let Some(tracked_room) = room_tracker.instances.remove(&instance) {
+            debug!("Room handle removed from {instance:?}, despawning room instance");
+            RoomSpawner::new(
+                &mut commands,
+                &registry,
+                &asset_server,
+                &mut events,
+                instance,
+            )
+            .despawn_room(tracked_room, &mut room_tracker.resources);
+        }
+    }
+
+    for event in asset_events.read() {
+        match event {
+            AssetEvent::Modified { id } => {
+                debug!("Room modified, reparsing room. Room:{:?}", id);
+
+                let Some(room) = room_assets.get(*id) else {
+                    continue;
+                };
+
+                room_tracker.validated.remove(id);
+                if !room_tracker.is_valid(
+                    *id,
+                    room,
+                    &registry,
+                    &asset_server,
+                    &mut events.invalid_room,
+                ) {
+                    continue;
+                }
+
+                let instances: Vec<Entity> = room_tracker
+                    .instances
+                    .iter()
+                    .filter(|(_, tracked_room)| tracked_room.room == *id)
+                    .map(|(instance, _)| *instance)
+                    .collect();
+
+                // The shared resources are only updated together with the first instance holding them
+                let mut resources_updated = false;
+                for instance in instances {
+                    if !report_unknown_prefabs(
+                        *id,
+                        instance,
+                        room,
+                        &registry,
+                        &mut events.unknown_prefab,
+                    ) {
+                        continue;
+                    }
+
+                    let old_room = room_tracker.instances.remove(&instance).unwrap();
+                    let update_resources = old_room.holds_resources && !resources_updated;
+                    resources_updated |= update_resources;
+                    let tracked_room = RoomSpawner::new(
+                        &mut commands,
+                        &registry,
+                        &asset_server,
+                        &mut events,
+                        instance,
+                    )
+                    .update_room(
+                        old_room,
+                        room,
+                        &mut room_tracker.resources,
+                        update_resources,
+                    );
+                    room_tracker.instances.insert(instance, tracked_room);
+                }
+            }
+            AssetEvent::Removed { id } | AssetEvent::Unused { id } => {
+                debug!("Room with handle {id:?} removed or unused");
+                room_tracker.validated.remove(id);
+
+                let instances: Vec<Entity> = room_tracker
+                    .instances
+                    .iter()
+                    .filter(|(_, tracked_room)| tracked_room.room == *id)
+                    .map(|(instance, _)| *instance)
+                    .collect();
+
+                for instance in instances {
+                    let tracked_room = room_tracker.instances.remove(&instance).unwrap();
+                    RoomSpawner::new(
+                        &mut commands,
+                        &registry,
+                        &asset_server,
+                        &mut events,
+                        instance,
+                    )
+                    .despawn_room(tracked_room, &mut room_tracker.resources);
+                }
+            }
+            AssetEvent::Added { id } => {
+                if let Some(room) = room_assets.get(*id) {
+                    room_tracker.is_valid(
+                        *id,
+                        room,
+                        &registry,
+                        &asset_server,
+                        &mut events.invalid_room,
+                    );
+                }
+            }
+            AssetEvent::LoadedWithDependencies { .. } => {}
+        }
+    }
+
+    for (instance, handle, has_transform) in &holders {
+        let id = handle.id();
+
+        match room_tracker.instances.get(&instance) {
+            Some(tracked_room) if tracked_room.room == id => continue,
+            Some(_) => {
+                debug!("Room handle of {instance:?} changed, despawning old room instance");
+                let tracked_room = room_tracker.instances.remove(&instance).unwrap();
+                RoomSpawner::new(
+                    &mut commands,
+                    &registry,
+                    &asset_server,
+                    &mut events,
+                    instance,
+                )
+                .despawn_room(tracked_room, &mut room_tracker.resources);
+            }
+            None => {}
+        }
+
+        let Some(room) = room_assets.get(id) else {
+            continue;
+        };
+
+        if room.settings.wait_for_dependencies {
+            match asset_server.get_recursive_dependency_load_state(id) {
+                Some(RecursiveDependencyLoadState::Loaded) => {}
+                Some(RecursiveDependencyLoadState::Failed) => {
+                    warn!("Some assets of room {id:?} failed to load, spawning it anyway")
+                }
+                _ => continue,
+            }
+        }
+
+        debug!("Spawning room {:?} for {:?}", id, instance);
+
+        if !has_transform {
+            commands.entity(instance).insert(SpatialBundle::default());
+        }
+
+        if !room_tracker.is_valid(id, room, &registry, &asset_server, &mut events.invalid_room)
+            || !report_unknown_prefabs(id, instance, room, &registry, &mut events.unknown_prefab)
+        {
+            room_tracker
+                .instances
+                .insert(instance, TrackedRoom::empty(id, instance));
+            continue;
+        }
+
+        let tracked_room = RoomSpawner::new(
+            &mut commands,
+            &registry,
+            &asset_server,
+            &mut events,
+            instance,
+        )
+        .spawn_room(id, room, &mut room_tracker.resources);
+        room_tracker.instances.insert(instance, tracked_room);
+    }
+}
+
+/// Sends an [UnknownPrefabType] event for every prefab in the room that is not registered.
+/// Returns false if the room should not be spawned because of the [UnknownPrefabPolicy].
+fn report_unknown_prefabs(
+    id: AssetId<Room>,
+    instance: Entity,
+    room: &Room,
+    registry: &PrefabRegistry,
+    unknown_events: &mut EventWriter<UnknownPrefabType>,
+) -> bool {
+    let mut unknown = Vec::new();
+    find_unknown_prefabs(None, &room.prefabs, registry, &mut unknown);
+
+    let policy = match room.settings.strict {
+        true => UnknownPrefabPolicy::FailRoom,
+        false => registry.unknown_prefab_policy(),
+    };
+
+    for (key, type_name) in &unknown {
+        match policy {
+            UnknownPrefabPolicy::Skip => {
+                warn!("Skipping prefab {} with unknown type {}", key, type_name)
+            }
+            UnknownPrefabPolicy::Placeholder => warn!(
+                "Spawning placeholder for prefab {} with unknown type {}",
+                key, type_name
+            ),
+            UnknownPrefabPolicy::FailRoom => error!(
+                "Room {:?} could not be spawned, prefab {} has unknown type {}",
+                id, key, type_name
+            ),
+        }
+    }
+
+    let room_allowed = unknown.is_empty() || policy != UnknownPrefabPolicy::FailRoom;
+
+    unknown_events.send_batch(
+        unknown
+            .into_iter()
+            .map(|(key, type_name)| UnknownPrefabType {
+                room: id,
+                instance,
+                key,
+                type_name,
+            }),
+    );
+
+    room_allowed
+}
+
+/// Collects the keys and types of all prefabs, including children, that are not registered
+fn find_unknown_prefabs(
+    parent_key: Option<&str>,
+    prefabs: &IndexMap<String, PrefabData>,
+    registry: &PrefabRegistry,
+    unknown: &mut Vec<(String, String)>,
+) {
+    for (name, prefab_data) in prefabs {
+        let key = child_key(parent_key, name);
+
+        if !prefab_data.prefab_type.is_empty() && !registry.contains(&prefab_data.prefab_type) {
+            unknown.push((key.clone(), prefab_data.prefab_type.clone()));
+        }
+
+        find_unknown_prefabs(Some(&key), &prefab_data.children, registry, unknown);
+    }
+}
+
+/// Spawns, updates and despawns the prefabs and resources of a single room instance
+struct RoomSpawner<'a, 'w, 's, 'e> {
+    commands: &'a mut Commands<'w, 's>,
+    registry: &'a PrefabRegistry,
+    asset_server: &'a AssetServer,
+    events: &'a mut RoomEvents<'e>,
+    instance: Entity,
+    /// The parent of the top level prefabs
+    root: Entity,
+    /// The entities of all prefabs in the room instance, used to resolve references between prefabs
+    entities: HashMap<String, Entity>,
+    /// The keys and entities of the prefabs spawned by this spawner
+    spawned: HashMap<String, Entity>,
+    /// The keys of the prefabs updated by this spawner
+    updated: Vec<String>,
+    /// The keys of the prefabs despawned by this spawner
+    removed: Vec<String>,
+}
+
+impl<'a, 'w, 's, 'e> RoomSpawner<'a, 'w, 's, 'e> {
+    fn new(
+        commands: &'a mut Commands<'w, 's>,
+        registry: &'a PrefabRegistry,
+        asset_server: &'a AssetServer,
+        events: &'a mut RoomEvents<'e>,
+        instance: Entity,
+    ) -> Self {
+        Self {
+            commands,
+            registry,
+            asset_server,
+            events,
+            instance,
+            root: instance,
+            entities: HashMap::new(),
+            spawned: HashMap::new(),
+            updated: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    /// Spawns the prefabs of a room as children of the instance entity,
+    /// the resources of the room are inserted if this is its first instance
+    fn spawn_room(
+        mut self,
+        id: AssetId<Room>,
+        room: &Room,
+        shared_resources: &mut HashMap<AssetId<Room>, SharedResources>,
+    ) -> TrackedRoom {
+        self.root = self.spawn_root(room.settings.offset);
+        self.reserve_entities(None, &room.prefabs, None);
+        let prepared = self.prepare_prefabs(None, &room.prefabs);
+        let prefabs = self.spawn_prefabs(None, Some(self.root), &prepared);
+        self.acquire_resources(id, room, shared_resources);
+
+        self.events.room_spawned.send(RoomSpawned {
+            room: id,
+            instance: self.instance,
+            entities: self.spawned,
+        });
+
+        TrackedRoom {
+            room: id,
+            root: self.root,
+            prefabs,
+            holds_resources: true,
+        }
+    }
+
+    /// Updates the prefabs of a reloaded room. The shared resources are diffed and updated
+    /// only if `update_resources` is set, so they are updated once for all instances.
+    fn update_room(
+        mut self,
+        old_room: TrackedRoom,
+        room: &Room,
+        shared_resources: &mut HashMap<AssetId<Room>, SharedResources>,
+        update_resources: bool,
+    ) -> TrackedRoom {
+        self.root = self.update_root(old_room.root, room.settings.offset, &old_room.prefabs);
+        self.reserve_entities(None, &room.prefabs, Some(&old_room.prefabs));
+        // References are compared after resolving them, so a retargeted reference is a change
+        let prepared = self.prepare_prefabs(None, &room.prefabs);
+        let old_prefabs = old_room
+            .prefabs
+            .iter()
+            .map(|(name, prefab)| (name.clone(), prefab.data.clone()))
+            .collect();
+        let patch = diff_prefabs(None, &old_prefabs, &prepared);
+        let prefabs =
+            self.update_prefabs(None, Some(self.root), old_room.prefabs, &prepared, &patch);
+
+        if !old_room.holds_resources {
+            self.acquire_resources(old_room.room, room, shared_resources);
+        } else if let Some(shared) = shared_resources
+            .get_mut(&old_room.room)
+            .filter(|_| update_resources)
+        {
+            let old_resources = std::mem::take(&mut shared.resources);
+            let new_resources = self.prepare_resources(&room.resources);
+            shared.resources = self.update_resources(old_resources, new_resources);
+        }
+
+        self.events.room_reloaded.send(RoomReloaded {
+            room: old_room.room,
+            instance: self.instance,
+            added: self.spawned,
+            updated: self.updated,
+            removed: self.removed,
+        });
+
+        TrackedRoom {
+            room: old_room.room,
+            root: self.root,
+            prefabs,
+            holds_resources: true,
+        }
+    }
+
+    /// Despawns all prefabs of a room instance that is no longer loaded,
+    /// the resources of the room are removed if this was its last instance
+    fn despawn_room(
+        mut self,
+        tracked_room: TrackedRoom,
+        shared_resources: &mut HashMap<AssetId<Room>, SharedResources>,
+    ) {
+        for (name, prefab) in tracked_room.prefabs {
+            self.despawn_prefab(&name, prefab);
+        }
+
+        if tracked_room.root != self.instance {
+            if let Some(entity_commands) = self.commands.get_entity(tracked_room.root) {
+                entity_commands.despawn_recursive();
+            }
+        }
+
+        if tracked_room.holds_resources {
+            self.release_resources(tracked_room.room, shared_resources);
+        }
+
+        self.events.room_despawned.send(RoomDespawned {
+            room: tracked_room.room,
+            instance: self.instance,
+        });
+    }
+
+    /// Spawns the [RoomOffset] entity for a room loaded with an offset,
+    /// returns the instance itself if the room has no offset
+    fn spawn_root(&mut self, offset: Transform) -> Entity {
+        if offset == Transform::IDENTITY {
+            return self.instance;
+        }
+
+        let root = self
+            .commands
+            .spawn((
+                SpatialBundle::from_transform(offset),
+                Name::new("Room offset"),
+                RoomOffset,
+            ))
+            .id();
+        self.commands.entity(self.instance).add_child(root);
+        root
+    }
+
+    /// Moves the [RoomOffset] entity to the offset of the reloaded room, the root is spawned
+    /// or despawned and the top level prefabs are moved to it if the room gains or loses its offset
+    fn update_root(
+        &mut self,
+        root: Entity,
+        offset: Transform,
+        prefabs: &IndexMap<String, TrackedPrefab>,
+    ) -> Entity {
+        if root != self.instance && offset != Transform::IDENTITY {
+            if let Some(mut entity_commands) = self.commands.get_entity(root) {
+                entity_commands.insert(offset);
+                return root;
+            }
+        }
+
+        let new_root = self.spawn_root(offset);
+        if new_root == root {
+            return root;
+        }
+
+        for prefab in prefabs.values() {
+            if self.commands.get_entity(prefab.entity).is_some() {
+                self.commands.entity(new_root).add_child(prefab.entity);
+            }
+        }
+        if root != self.instance {
+            if let Some(entity_commands) = self.commands.get_entity(root) {
+                entity_commands.despawn_recursive();
+            }
+        }
+        new_root
+    }
+
+    /// Counts the instance as a holder of the resources of the room, inserting them for the first instance
+    fn acquire_resources(
+        &mut self,
+        id: AssetId<Room>,
+        room: &Room,
+        shared_resources: &mut HashMap<AssetId<Room>, SharedResources>,
+    ) {
+        if let Some(shared) = shared_resources.get_mut(&id) {
+            shared.instances += 1;
+            return;
+        }
+
+        let resources = self
+            .prepare_resources(&room.resources)
+            .into_iter()
+            .filter(|(key, resource_data)| {
+                self.registry
+                    .insert_resource(key, resource_data, self.commands, self.asset_server)
+            })
+            .collect();
+        shared_resources.insert(
+            id,
+            SharedResources {
+                instances: 1,
+                resources,
+            },
+        );
+    }
+
+    /// Stops counting the instance as a holder of the resources of the room,
+    /// removing them after the last instance
+    fn release_resources(
+        &mut self,
+        id: AssetId<Room>,
+        shared_resources: &mut HashMap<AssetId<Room>, SharedResources>,
+    ) {
+        let Some(shared) = shared_resources.get_mut(&id) else {
+            return;
+        };
+
+        shared.instances -= 1;
+        if shared.instances > 0 {
+            return;
+        }
+
+        for (_, resource_data) in shared_resources.remove(&id).unwrap().resources {
+            self.registry
+                .remove_resource(&resource_data.prefab_type, self.commands);
+        }
+    }
+
+    /// Reserves an entity for every prefab that will be spawned, so references between prefabs
+    /// can be resolved before any of them is spawned. Prefabs that are kept on reload keep their entity.
+    fn reserve_entities(
+        &mut self,
+        parent_key: Option<&str>,
+        prefabs: &IndexMap<String, PrefabData>,
+        old_prefabs: Option<&IndexMap<String, TrackedPrefab>>,
+    ) {
+        for (name, prefab_data) in prefabs {
+            let key = child_key(parent_key, name);
+            // Prefabs that are respawned get new entities for their children as well
+            let kept_prefab = old_prefabs
+                .and_then(|old_prefabs| old_prefabs.get(name))
+                .filter(|old_prefab| {
+                    old_prefab.data.prefab_type == prefab_data.prefab_type
+                        && self.commands.get_entity(old_prefab.entity).is_some()
+                });
+
+            let entity = match kept_prefab {
+                Some(kept_prefab) => kept_prefab.entity,
+                None if self.is_spawnable(prefab_data) => self.commands.spawn_empty().id(),
+                None => continue,
+            };
+
+            self.entities.insert(key.clone(), entity);
+            self.reserve_entities(
+                Some(&key),
+                &prefab_data.children,
+                kept_prefab.map(|kept_prefab| &kept_prefab.children),
+            );
+        }
+    }
+
+    /// Returns false if the prefab is skipped because its type is unknown
+    fn is_spawnable(&self, prefab_data: &PrefabData) -> bool {
+        self.registry.contains(&prefab_data.prefab_type)
+            || prefab_data.prefab_type.is_empty()
+            || self.registry.unknown_prefab_policy() == UnknownPrefabPolicy::Placeholder
+    }
+
+    /// Returns the prefabs and their children with the defaults of their schemas filled in
+    /// and every reference resolved to the entity of the referenced prefab.
+    /// Prefabs that are skipped because their type is unknown are left unchanged.
+    fn prepare_prefabs(
+        &self,
+        parent_key: Option<&str>,
+        prefabs: &IndexMap<String, PrefabData>,
+    ) -> IndexMap<String, PrefabData> {
+        prefabs
+            .iter()
+            .map(|(name, prefab_data)| {
+                let key = child_key(parent_key, name);
+                let mut prefab_data = prefab_data.clone();
+                if !self.entities.contains_key(&key) {
+                    return (name.clone(), prefab_data);
+                }
+
+                if let Some(schema) = self.registry.prefab_schema(&prefab_data.prefab_type) {
+                    schema.apply_defaults(&mut prefab_data.fields);
+                }
+
+                let mut unresolved = Vec::new();
+
+                for field in prefab_data.fields.values_mut() {
+                    field.resolve_refs(&self.entities, &mut unresolved);
+                }
+
+                for target in unresolved {
+                    warn!("Prefab {} references the unknown prefab {}", key, target);
+                }
+
+                prefab_data.children = self.prepare_prefabs(Some(&key), &prefab_data.children);
+                (name.clone(), prefab_data)
+            })
+            .collect()
+    }
+
+    fn spawn_prefabs(
+        &mut self,
+        parent_key: Option<&str>,
+        parent: Option<Entity>,
+        prefabs: &IndexMap<String, PrefabData>,
+    ) -> IndexMap<String, TrackedPrefab> {
+        prefabs
+            .iter()
+            .filter_map(|(name, prefab_data)| {
+                let key = child_key(parent_key, name);
+                let prefab = self.spawn_prefab(&key, prefab_data, parent)?;
+                Some((name.clone(), prefab))
+            })
+            .collect()
+    }
+
+    /// Spawns a single prepared prefab and its children into the entity reserved for it,
+    /// returns `None` if the prefab was skipped because its type is unknown
+    fn spawn_prefab(
+        &mut self,
+        key: &str,
+        prefab_data: &PrefabData,
+        parent: Option<Entity>,
+    ) -> Option<TrackedPrefab> {
+        let entity = *self.entities.get(key)?;
+
+        if self.registry.contains(&prefab_data.prefab_type) {
+            self.registry.spawn(
+                key,
+                prefab_data,
+                self.commands.entity(entity),
+                self.asset_server,
+            );
+        } else if prefab_data.prefab_type.is_empty() {
+            self.commands
+                .entity(entity)
+                .insert(Name::new(key.to_string()));
+        } else {
+            self.commands
+                .entity(entity)
+                .insert((Name::new(key.to_string()), MissingPrefab::new(prefab_data)));
+        }
+
+        if !prefab_data.components.is_empty() {
+            self.commands.entity(entity).add(insert_components(
+                prefab_data.components.values().cloned().collect(),
+            ));
+        }
+
+        if let Some(parent) = parent {
+            self.commands.entity(parent).add_child(entity);
+        }
+
+        self.spawned.insert(key.to_string(), entity);
+        self.events.prefab_spawned.send(PrefabSpawned {
+            instance: self.instance,
+            key: key.to_string(),
+            prefab_type: prefab_data.prefab_type.clone(),
+            entity,
+        });
+
+        let children = self.spawn_prefabs(Some(key), Some(entity), &prefab_data.children);
+
+        Some(TrackedPrefab {
+            entity,
+            data: prefab_data.clone(),
+            children,
+        })
+    }
+
+    /// Applies the patch of a reloaded room to the prefabs that were spawned before
+    fn update_prefabs(
+        &mut self,
+        parent_key: Option<&str>,
+        parent: Option<Entity>,
+        mut old_prefabs: IndexMap<String, TrackedPrefab>,
+        new_prefabs: &IndexMap<String, PrefabData>,
+        patch: &PrefabsPatch,
+    ) -> IndexMap<String, TrackedPrefab> {
+        let changes: HashMap<&str, &PrefabPatch> = patch
+            .changes
+            .iter()
+            .map(|change| (change.name(), change))
+            .collect();
+
+        for change in &patch.changes {
+            if let PrefabPatch::Removed { name } = change {
+                if let Some(old_prefab) = old_prefabs.shift_remove(name) {
+                    self.despawn_prefab(&child_key(parent_key, name), old_prefab);
+                }
+            }
+        }
+
+        let unchanged = PrefabsPatch::default();
+        new_prefabs
+            .iter()
+            .filter_map(|(name, new_prefab)| {
+                let key = child_key(parent_key, name);
+                let change = changes.get(name.as_str()).copied();
+                let prefab = match (old_prefabs.shift_remove(name), change) {
+                    (None, _) => self.spawn_prefab(&key, new_prefab, parent),
+                    (Some(old_prefab), Some(PrefabPatch::Retyped { old_type, .. })) => {
+                        debug!(
+                            "Prefab {} changed type from {} to {}, respawning it",
+                            key, old_type, new_prefab.prefab_type
+                        );
+                        self.despawn_prefab(&key, old_prefab);
+                        self.spawn_prefab(&key, new_prefab, parent)
+                    }
+                    (Some(old_prefab), _)
+                        if self.entities.get(&key) != Some(&old_prefab.entity) =>
+                    {
+                        debug!(
+                            "Prefab {} was despawned outside of its room, respawning it",
+                            key
+                        );
+                        self.spawn_prefab(&key, new_prefab, parent)
+                    }
+                    (
+                        Some(old_prefab),
+                        Some(PrefabPatch::Changed {
+                            fields,
+                            components,
+                            children,
+                            ..
+                        }),
+                    ) => Some(self.update_prefab(
+                        &key,
+                        old_prefab,
+                        new_prefab,
+                        Some((fields, components)),
+                        children,
+                    )),
+                    // Unchanged prefabs are still visited to respawn children despawned outside of the room
+                    (Some(old_prefab), _) => {
+                        Some(self.update_prefab(&key, old_prefab, new_prefab, None, &unchanged))
+                    }
+                }?;
+                Some((name.clone(), prefab))
+            })
+            .collect()
+    }
+
+    /// Applies the changes to the fields and components of a kept prefab and updates its children
+    fn update_prefab(
+        &mut self,
+        key: &str,
+        old_prefab: TrackedPrefab,
+        new_prefab: &PrefabData,
+        changes: Option<(&PrefabChangeSet, &ComponentChangeSet)>,
+        children: &PrefabsPatch,
+    ) -> TrackedPrefab {
+        let entity = old_prefab.entity;
+
+        // Prefabs whose children changed are patched without changes of their own
+        let changes =
+            changes.filter(|(fields, components)| !fields.is_empty() || !components.is_empty());
+        if let Some((fields, components)) = changes {
+            if !fields.is_empty() && self.registry.contains(&new_prefab.prefab_type) {
+                self.registry.update(
+                    &new_prefab.prefab_type,
+                    fields,
+                    self.commands.entity(entity),
+                    self.asset_server,
+                );
+            } else if !fields.is_empty() && !new_prefab.prefab_type.is_empty() {
+                self.commands
+                    .entity(entity)
+                    .insert(MissingPrefab::new(new_prefab));
+            }
+
+            // Reflected components are patched in place so runtime state of other fields is kept
+            if let Some(patch) = patch_components(components) {
+                self.commands.entity(entity).add(patch);
+            }
+
+            self.updated.push(key.to_string());
+            self.events.prefab_updated.send(PrefabUpdated {
+                instance: self.instance,
+                prefab_type: new_prefab.prefab_type.clone(),
+                entity,
+                changes: fields.clone(),
+            });
+        }
+
+        let children = self.update_prefabs(
+            Some(key),
+            Some(entity),
+            old_prefab.children,
+            &new_prefab.children,
+            children,
+        );
+
+        TrackedPrefab {
+            entity,
+            data: new_prefab.clone(),
+            children,
+        }
+    }
+
+    /// Despawns a prefab together with all of its children,
+    /// the prefab may already be gone if the instance entity was despawned recursively
+    fn despawn_prefab(&mut self, key: &str, prefab: TrackedPrefab) {
+        if let Some(entity_commands) = self.commands.get_entity(prefab.entity) {
+            entity_commands.despawn_recursive();
+        }
+
+        self.removed.push(key.to_string());
+        self.record_removed_children(key, &prefab.children);
+    }
+
+    /// Records the keys of the children of a despawned prefab as removed
+    fn record_removed_children(&mut self, key: &str, children: &IndexMap<String, TrackedPrefab>) {
+        for (name, child) in children {
+            let child_key = child_key(Some(key), name);
+            self.record_removed_children(&child_key, &child.children);
+            self.removed.push(child_key);
+        }
+    }
+
+    /// Returns the resources with the defaults of their schemas filled in
+    fn prepare_resources(
+        &self,
+        resources: &IndexMap<String, PrefabData>,
+    ) -> IndexMap<String, PrefabData> {
+        let mut resources = resources.clone();
+        for resource_data in resources.values_mut() {
+            if let Some(schema) = self.registry.resource_schema(&resource_data.prefab_type) {
+                schema.apply_defaults(&mut resource_data.fields);
+            }
+        }
+        resources
+    }
+
+    /// Applies the patch of the resources of a reloaded room to the resources that were inserted before
+    fn update_resources(
+        &mut self,
+        old_resources: IndexMap<String, PrefabData>,
+        new_resources: IndexMap<String, PrefabData>,
+    ) -> IndexMap<String, PrefabData> {
+        let patch = diff_prefabs(None, &old_resources, &new_resources);
+        let mut inserted = Vec::new();
+
+        for change in &patch.changes {
+            match change {
+                PrefabPatch::Removed { name } => {
+                    self.registry
+                        .remove_resource(&old_resources[name].prefab_type, self.commands);
+                }
+                PrefabPatch::Retyped { name, old_type, .. } => {
+                    self.registry.remove_resource(old_type, self.commands);
+                    inserted.push(name.as_str());
+                }
+                PrefabPatch::Added { name, .. } => inserted.push(name.as_str()),
+                PrefabPatch::Changed { name, fields, .. } if !fields.is_empty() => {
+                    self.registry.update_resource(
+                        &new_resources[name].prefab_type,
+                        fields,
+                        self.commands,
+                        self.asset_server,
+                    );
+                }
+                PrefabPatch::Changed { .. } => {}
+            }
+        }
+
+        new_resources
+            .iter()
+            .filter(|(key, new_resource)| {
+                !inserted.contains(&key.as_str())
+                    || self.registry.insert_resource(
+                        key,
+                        new_resource,
+                        self.commands,
+                        self.asset_server,
+                    )
+            })
+            .map(|(key, new_resource)| (key.clone(), new_resource.clone()))
+            .collect()
+    }
+}
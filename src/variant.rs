@@ -0,0 +1,304 @@
+use indexmap::IndexMap;
+
+use crate::room::{child_key, LoadRoomError, PrefabData, Room};
+
+/// Flattens every prefab and resource that extends a base entry into full [PrefabData]
+/// and removes the template entries that are only used as bases.
+pub(crate) fn resolve_variants(room: &mut Room) -> Result<(), LoadRoomError> {
+    room.prefabs = flatten_prefabs(None, &room.prefabs, &room.prefabs)?;
+    room.resources = flatten_prefabs(None, &room.resources, &room.resources)?;
+    Ok(())
+}
+
+fn flatten_prefabs(
+    parent_key: Option<&str>,
+    prefabs: &IndexMap<String, PrefabData>,
+    root: &IndexMap<String, PrefabData>,
+) -> Result<IndexMap<String, PrefabData>, LoadRoomError> {
+    prefabs
+        .iter()
+        .filter(|(_, prefab_data)| !prefab_data.template)
+        .map(|(name, prefab_data)| {
+            let key = child_key(parent_key, name);
+            let mut chain = vec![name.clone()];
+            let mut flattened = flatten_prefab(&key, prefab_data, prefabs, root, &mut chain)?;
+            flattened.children = flatten_prefabs(Some(&key), &flattened.children, root)?;
+            Ok((name.clone(), flattened))
+        })
+        .collect()
+}
+
+/// Merges a prefab with the chain of bases it extends.
+/// Bases are looked up among the siblings of the prefab first and then among the top level entries.
+fn flatten_prefab(
+    key: &str,
+    prefab_data: &PrefabData,
+    siblings: &IndexMap<String, PrefabData>,
+    root: &IndexMap<String, PrefabData>,
+    chain: &mut Vec<String>,
+) -> Result<PrefabData, LoadRoomError> {
+    let Some(base_name) = &prefab_data.extends else {
+        if prefab_data.prefab_type.is_empty() && prefab_data.components.is_empty() {
+            return Err(LoadRoomError::MissingType(key.to_string()));
+        }
+        return Ok(prefab_data.clone());
+    };
+
+    if chain.contains(base_name) {
+        return Err(LoadRoomError::ExtendsCycle(format!(
+            "{} -> {}",
+            chain.join(" -> "),
+            base_name
+        )));
+    }
+
+    let (base, base_siblings) = match siblings.get(base_name) {
+        Some(base) => (base, siblings),
+        None => match root.get(base_name) {
+            Some(base) => (base, root),
+            None => {
+                return Err(LoadRoomError::UnknownBase {
+                    key: key.to_string(),
+                    base: base_name.clone(),
+                })
+            }
+        },
+    };
+
+    chain.push(base_name.clone());
+    let base = flatten_prefab(key, base, base_siblings, root, chain)?;
+    chain.pop();
+
+    let mut fields = base.fields;
+    fields.extend(prefab_data.fields.clone());
+
+    let mut components = base.components;
+    components.extend(prefab_data.components.clone());
+
+    let mut children = base.children;
+    children.extend(prefab_data.children.clone());
+
+    let mut profiles = base.profiles;
+    profiles.extend(prefab_data.profiles.clone());
+
+    Ok(PrefabData {
+        prefab_type: match prefab_data.prefab_type.is_empty() {
+            true => base.prefab_type,
+            false => prefab_data.prefab_type.clone(),
+        },
+        fields,
+        components,
+        children,
+        profiles,
+        extends: prefab_data.extends.clone(),
+        template: false,
+        order: prefab_data.order.or(base.order),
+        after: match prefab_data.after.is_empty() {
+            true => base.after,
+            false => prefab_data.after.clone(),
+        },
+    })
+}
+
+/// Merges the overrides of the selected profile into every prefab and resource
+/// and drops the overrides of all other profiles
+pub(crate) fn apply_profile(room: &mut Room, profile: Option<&str>) {
+    for prefab_data in room.prefabs.values_mut().chain(room.resources.values_mut()) {
+        apply_prefab_profile(prefab_data, profile);
+    }
+}
+
+fn apply_prefab_profile(prefab_data: &mut PrefabData, profile: Option<&str>) {
+    let mut profiles = std::mem::take(&mut prefab_data.profiles);
+
+    if let Some(overrides) = profile.and_then(|profile| profiles.shift_remove(profile)) {
+        if !overrides.prefab_type.is_empty() {
+            prefab_data.prefab_type = overrides.prefab_type;
+        }
+        prefab_data.fields.extend(overrides.fields);
+        prefab_data.components.extend(overrides.components);
+        prefab_data.children.extend(overrides.children);
+    }
+
+    for child in prefab_data.children.values_mut() {
+        apply_prefab_profile(child, profile);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::reflect::AppTypeRegistry;
+
+    use super::*;
+    use crate::{format::RoomFormat, room::PrefabField};
+
+    fn resolve(room: &str) -> Result<Room, LoadRoomError> {
+        let mut room = RoomFormat::Ron.parse(room.as_bytes(), &AppTypeRegistry::default())?;
+        resolve_variants(&mut room)?;
+        Ok(room)
+    }
+
+    fn string(value: &str) -> PrefabField {
+        PrefabField::String(value.to_string())
+    }
+
+    #[test]
+    fn variant_overrides_base() {
+        let room = resolve(
+            r#"(prefabs: {
+                "base": (type: "Pig", fields: { "name": "base", "color": "pink" }, children: {
+                    "tail": (type: "Tail"),
+                }),
+                "variant": (extends: "base", fields: { "name": "variant" }, children: {
+                    "hat": (type: "Hat"),
+                }),
+            })"#,
+        )
+        .unwrap();
+
+        let variant = &room.prefabs["variant"];
+        assert_eq!(variant.prefab_type, "Pig");
+        assert_eq!(variant.fields["name"], string("variant"));
+        assert_eq!(variant.fields["color"], string("pink"));
+        let children: Vec<&str> = variant.children.keys().map(String::as_str).collect();
+        assert_eq!(children, ["tail", "hat"]);
+        assert_eq!(room.prefabs["base"].fields["name"], string("base"));
+    }
+
+    #[test]
+    fn variants_extend_chains_and_drop_templates() {
+        let room = resolve(
+            r#"(prefabs: {
+                "animal": (type: "Animal", template: true, fields: { "legs": "four", "name": "animal" }),
+                "pig": (extends: "animal", template: true, fields: { "name": "pig" }),
+                "piglet": (extends: "pig", type: "Piglet"),
+            })"#,
+        )
+        .unwrap();
+
+        let keys: Vec<&str> = room.prefabs.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["piglet"]);
+        let piglet = &room.prefabs["piglet"];
+        assert_eq!(piglet.prefab_type, "Piglet");
+        assert_eq!(piglet.fields["legs"], string("four"));
+        assert_eq!(piglet.fields["name"], string("pig"));
+        assert!(!piglet.template);
+    }
+
+    #[test]
+    fn children_extend_siblings_before_top_level_entries() {
+        let room = resolve(
+            r#"(prefabs: {
+                "hat": (type: "TopHat", template: true),
+                "pen": (type: "Pen", children: {
+                    "hat": (type: "Cap", template: true),
+                    "pig": (extends: "hat"),
+                    "other": (extends: "pen_base"),
+                }),
+                "pen_base": (type: "Fence", template: true),
+            })"#,
+        )
+        .unwrap();
+
+        let children = &room.prefabs["pen"].children;
+        assert_eq!(children["pig"].prefab_type, "Cap");
+        assert_eq!(children["other"].prefab_type, "Fence");
+        assert!(!children.contains_key("hat"));
+    }
+
+    #[test]
+    fn unknown_base_is_an_error() {
+        let error = resolve(r#"(prefabs: { "pig": (extends: "missing") })"#).unwrap_err();
+        assert!(matches!(
+            error,
+            LoadRoomError::UnknownBase { key, base } if key == "pig" && base == "missing"
+        ));
+    }
+
+    #[test]
+    fn extends_cycle_is_an_error() {
+        let error = resolve(
+            r#"(prefabs: {
+                "a": (extends: "b"),
+                "b": (extends: "a"),
+            })"#,
+        )
+        .unwrap_err();
+        assert!(matches!(error, LoadRoomError::ExtendsCycle(cycle) if cycle == "a -> b -> a"));
+    }
+
+    #[test]
+    fn prefab_without_type_is_an_error() {
+        let error = resolve(r#"(prefabs: { "pig": (fields: { "name": "pig" }) })"#).unwrap_err();
+        assert!(matches!(error, LoadRoomError::MissingType(key) if key == "pig"));
+    }
+
+    fn profiled(room: &str, profile: Option<&str>) -> Room {
+        let mut room = resolve(room).unwrap();
+        apply_profile(&mut room, profile);
+        room
+    }
+
+    const PROFILED_ROOM: &str = r#"(
+        prefabs: {
+            "pig": (type: "Pig", fields: { "name": "pig", "color": "pink" }, profiles: {
+                "hard": (type: "Boar", fields: { "color": "brown" }, children: {
+                    "tusks": (type: "Tusks"),
+                }),
+                "easy": (fields: { "color": "white" }),
+            }, children: {
+                "tail": (type: "Tail", profiles: { "hard": (fields: { "curly": "no" }) }),
+            }),
+            "piglet": (extends: "pig", fields: { "name": "piglet" }),
+        },
+        resources: {
+            "money": (type: "Money", fields: { "amount": "100" }, profiles: {
+                "hard": (fields: { "amount": "10" }),
+            }),
+        },
+    )"#;
+
+    #[test]
+    fn profile_overrides_type_fields_and_children() {
+        let room = profiled(PROFILED_ROOM, Some("hard"));
+
+        let pig = &room.prefabs["pig"];
+        assert_eq!(pig.prefab_type, "Boar");
+        assert_eq!(pig.fields["name"], string("pig"));
+        assert_eq!(pig.fields["color"], string("brown"));
+        let children: Vec<&str> = pig.children.keys().map(String::as_str).collect();
+        assert_eq!(children, ["tail", "tusks"]);
+        assert!(pig.profiles.is_empty());
+    }
+
+    #[test]
+    fn profiles_apply_to_children_resources_and_variants() {
+        let room = profiled(PROFILED_ROOM, Some("hard"));
+
+        assert_eq!(
+            room.prefabs["pig"].children["tail"].fields["curly"],
+            string("no")
+        );
+        assert_eq!(room.resources["money"].fields["amount"], string("10"));
+
+        let piglet = &room.prefabs["piglet"];
+        assert_eq!(piglet.prefab_type, "Boar");
+        assert_eq!(piglet.fields["name"], string("piglet"));
+        assert_eq!(piglet.fields["color"], string("brown"));
+    }
+
+    #[test]
+    fn other_profiles_are_dropped() {
+        for profile in [None, Some("missing")] {
+            let room = profiled(PROFILED_ROOM, profile);
+
+            let pig = &room.prefabs["pig"];
+            assert_eq!(pig.prefab_type, "Pig");
+            assert_eq!(pig.fields["color"], string("pink"));
+            assert!(pig.profiles.is_empty());
+            assert!(!pig.children.contains_key("tusks"));
+            assert!(pig.children["tail"].profiles.is_empty());
+            assert_eq!(room.resources["money"].fields["amount"], string("100"));
+        }
+    }
+}
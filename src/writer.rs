@@ -0,0 +1,195 @@
+use std::path::Path;
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use indexmap::IndexMap;
+use ron::ser::PrettyConfig;
+use thiserror::Error;
+
+use crate::{
+    room::{PrefabData, PrefabField, PrefabRegistry, Room, RoomLoaderSettings},
+    schema::PrefabSchema,
+    spawner::{RoomTracker, TrackedPrefab},
+};
+
+/// An error returned when a room could not be saved
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum WriteRoomError {
+    /// No room has been spawned for the entity
+    #[error("No room instance is spawned for {0:?}")]
+    NotSpawned(Entity),
+    /// An [IO](std::io) Error
+    #[error("Could not write room: {0}")]
+    Io(#[from] std::io::Error),
+    /// A [RON](ron) Error
+    #[error("Could not serialize RON: {0}")]
+    Ron(#[from] ron::Error),
+}
+
+/// A system parameter that serializes the live entities of a spawned room back into a room file.
+///
+/// The fields of every prefab are read with [Prefab::extract](crate::room::Prefab::extract)
+/// and reflected components are written with their current value.
+/// Includes, variants and the loaded profile are written flattened, the same way they are spawned.
+///
+/// The room is written the way it was before the [RoomLoaderSettings] were applied,
+/// so loading it with the same settings spawns the entities where they are now:
+/// the key prefix is removed from keys and references, and fields that still have the default
+/// of their schema are left out.
+#[derive(SystemParam)]
+pub struct RoomWriter<'w> {
+    world: &'w World,
+}
+
+impl RoomWriter<'_> {
+    /// Builds a room from the current state of the entities spawned for the room instance
+    /// held by the given entity
+    pub fn extract_room(&self, instance: Entity) -> Result<Room, WriteRoomError> {
+        let room_tracker = self.world.resource::<RoomTracker>();
+        let tracked_room = room_tracker
+            .instances
+            .get(&instance)
+            .ok_or(WriteRoomError::NotSpawned(instance))?;
+
+        let settings = self
+            .world
+            .resource::<Assets<Room>>()
+            .get(tracked_room.room)
+            .map(|room| room.settings.clone())
+            .unwrap_or_default();
+        let registry = self.world.resource::<PrefabRegistry>();
+
+        let prefabs = self.extract_prefabs(&tracked_room.prefabs);
+        let mut resources = room_tracker
+            .resources
+            .get(&tracked_room.room)
+            .map(|shared| shared.resources.clone())
+            .unwrap_or_default();
+        for resource_data in resources.values_mut() {
+            if let Some(schema) = registry.resource_schema(&resource_data.prefab_type) {
+                remove_defaults(schema, &mut resource_data.fields);
+            }
+        }
+
+        Ok(Room {
+            includes: Vec::new(),
+            prefabs: strip_key_prefix(&settings.key_prefix, prefabs),
+            resources,
+            settings: RoomLoaderSettings::default(),
+            dependencies: Vec::new(),
+        })
+    }
+
+    /// Serializes the current state of the given room instance to RON
+    pub fn room_to_ron(&self, instance: Entity) -> Result<String, WriteRoomError> {
+        let room = self.extract_room(instance)?;
+        Ok(ron::ser::to_string_pretty(&room, PrettyConfig::default())?)
+    }
+
+    /// Writes the current state of the given room instance to a ron file
+    pub fn write_room(
+        &self,
+        instance: Entity,
+        path: impl AsRef<Path>,
+    ) -> Result<(), WriteRoomError> {
+        let ron = self.room_to_ron(instance)?;
+        std::fs::write(path, ron)?;
+        Ok(())
+    }
+
+    /// Extracts the prefabs and their children
+    fn extract_prefabs(
+        &self,
+        prefabs: &IndexMap<String, TrackedPrefab>,
+    ) -> IndexMap<String, PrefabData> {
+        let registry = self.world.resource::<PrefabRegistry>();
+
+        prefabs
+            .iter()
+            .map(|(name, prefab)| {
+                let entity = self.world.get_entity(prefab.entity);
+                let prefab_type = &prefab.data.prefab_type;
+
+                let mut fields = prefab.data.fields.clone();
+                if let Some(entity) = entity {
+                    fields.extend(registry.extract(prefab_type, entity));
+                }
+                if let Some(schema) = registry.prefab_schema(prefab_type) {
+                    remove_defaults(schema, &mut fields);
+                }
+
+                let components = prefab
+                    .data
+                    .components
+                    .iter()
+                    .map(|(type_path, component)| {
+                        let component = entity
+                            .and_then(|entity| component.extract(entity))
+                            .unwrap_or_else(|| component.clone());
+                        (type_path.clone(), component)
+                    })
+                    .collect();
+
+                let prefab_data = PrefabData {
+                    prefab_type: prefab_type.clone(),
+                    fields,
+                    components,
+                    children: self.extract_prefabs(&prefab.children),
+                    profiles: IndexMap::new(),
+                    extends: None,
+                    template: false,
+                    order: prefab.data.order,
+                    after: prefab.data.after.clone(),
+                };
+                (name.clone(), prefab_data)
+            })
+            .collect()
+    }
+}
+
+/// Removes the fields that still have the default value of their schema
+fn remove_defaults(schema: &PrefabSchema, fields: &mut HashMap<String, PrefabField>) {
+    fields.retain(|name, field| {
+        schema
+            .field(name)
+            .and_then(|field_schema| field_schema.default())
+            != Some(field)
+    });
+}
+
+/// Removes the prefix the room was loaded with from the keys of the top level prefabs
+/// and from every reference, the reverse of prefixing the keys when the room is loaded
+fn strip_key_prefix(
+    prefix: &str,
+    prefabs: IndexMap<String, PrefabData>,
+) -> IndexMap<String, PrefabData> {
+    fn strip_refs(prefix: &str, prefab_data: &mut PrefabData) {
+        for field in prefab_data.fields.values_mut() {
+            field.strip_ref_prefix(prefix);
+        }
+        for child in prefab_data.children.values_mut() {
+            strip_refs(prefix, child);
+        }
+    }
+
+    if prefix.is_empty() {
+        return prefabs;
+    }
+
+    prefabs
+        .into_iter()
+        .map(|(name, mut prefab_data)| {
+            strip_refs(prefix, &mut prefab_data);
+            for after in &mut prefab_data.after {
+                if let Some(key) = after.strip_prefix(prefix) {
+                    *after = key.to_string();
+                }
+            }
+            let name = name
+                .strip_prefix(prefix)
+                .map(str::to_string)
+                .unwrap_or(name);
+            (name, prefab_data)
+        })
+        .collect()
+}
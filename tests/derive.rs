@@ -0,0 +1,189 @@
+use bevy::{ecs::system::CommandQueue, prelude::*, utils::HashMap};
+use hana_prefab::room::{
+    DerivedPrefab, Prefab, PrefabChangeSet, PrefabField, PrefabFields, PrefabRegistry,
+};
+
+#[derive(Asset, TypePath)]
+struct Sprite;
+
+#[derive(Component)]
+struct Marker;
+
+#[derive(Component, Debug, PartialEq)]
+struct Speed(f32);
+
+impl From<f32> for Speed {
+    fn from(speed: f32) -> Self {
+        Self(speed)
+    }
+}
+
+#[derive(Component, Debug, PartialEq)]
+struct Label(String);
+
+impl From<String> for Label {
+    fn from(label: String) -> Self {
+        Self(label)
+    }
+}
+
+#[derive(Prefab)]
+#[prefab(bundle = "marker", extract = "extract_speed")]
+struct TestPrefab {
+    #[prefab(asset)]
+    sprite: Handle<Sprite>,
+    #[prefab(with = "position_transform")]
+    position: Vec2,
+    #[prefab(component = Speed, default = "default_speed")]
+    speed: f32,
+    #[prefab(component = Label, rename = "name")]
+    label: Option<String>,
+}
+
+fn marker() -> Marker {
+    Marker
+}
+
+fn position_transform(position: Vec2) -> Transform {
+    Transform::from_translation(position.extend(0.0))
+}
+
+fn default_speed() -> f32 {
+    300.0
+}
+
+fn extract_speed(entity: EntityRef) -> HashMap<String, PrefabField> {
+    entity
+        .get::<Speed>()
+        .map(|speed| ("speed".to_string(), PrefabField::Number(speed.0)))
+        .into_iter()
+        .collect()
+}
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Sprite>();
+    app
+}
+
+fn fields(fields: &[(&str, PrefabField)]) -> HashMap<String, PrefabField> {
+    fields
+        .iter()
+        .map(|(name, field)| (name.to_string(), field.clone()))
+        .collect()
+}
+
+fn spawn(app: &mut App, fields: &HashMap<String, PrefabField>) -> Entity {
+    let world = &mut app.world;
+    let entity = world.spawn_empty().id();
+    let asset_server = world.resource::<AssetServer>().clone();
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, world);
+    TestPrefab::spawn(
+        &PrefabFields::new("test", fields),
+        commands.entity(entity),
+        &asset_server,
+    );
+    queue.apply(world);
+    entity
+}
+
+fn update(app: &mut App, entity: Entity, changes: &PrefabChangeSet) {
+    let world = &mut app.world;
+    let asset_server = world.resource::<AssetServer>().clone();
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, world);
+    TestPrefab::update(changes, &asset_server, commands.entity(entity));
+    queue.apply(world);
+}
+
+#[test]
+fn spawns_fields_as_components() {
+    let mut app = app();
+    let fields = fields(&[
+        ("sprite", PrefabField::String("sprite.png".into())),
+        ("position", PrefabField::Vec2(1.0, 2.0)),
+        ("name", PrefabField::String("player".into())),
+    ]);
+    let entity = spawn(&mut app, &fields);
+
+    let entity = app.world.entity(entity);
+    assert!(entity.contains::<Marker>());
+    assert!(entity.contains::<Handle<Sprite>>());
+    assert_eq!(
+        entity.get::<Transform>().unwrap().translation,
+        Vec3::new(1.0, 2.0, 0.0)
+    );
+    assert_eq!(entity.get::<Speed>(), Some(&Speed(300.0)));
+    assert_eq!(entity.get::<Label>(), Some(&Label("player".into())));
+}
+
+#[test]
+fn does_not_spawn_without_required_fields() {
+    let mut app = app();
+    let entity = spawn(
+        &mut app,
+        &fields(&[("position", PrefabField::Vec2(0.0, 0.0))]),
+    );
+
+    let entity = app.world.entity(entity);
+    assert!(!entity.contains::<Marker>());
+    assert!(!entity.contains::<Transform>());
+}
+
+#[test]
+fn updates_changed_and_removed_fields() {
+    let mut app = app();
+    let old = fields(&[
+        ("sprite", PrefabField::String("sprite.png".into())),
+        ("position", PrefabField::Vec2(0.0, 0.0)),
+        ("speed", PrefabField::Number(10.0)),
+        ("name", PrefabField::String("player".into())),
+    ]);
+    let entity = spawn(&mut app, &old);
+
+    let mut new = old.clone();
+    new.insert("position".into(), PrefabField::Vec2(3.0, 4.0));
+    new.remove("speed");
+    new.remove("name");
+    update(&mut app, entity, &PrefabChangeSet::new("test", &old, &new));
+
+    let entity = app.world.entity(entity);
+    assert_eq!(
+        entity.get::<Transform>().unwrap().translation,
+        Vec3::new(3.0, 4.0, 0.0)
+    );
+    assert_eq!(entity.get::<Speed>(), Some(&Speed(300.0)));
+    assert!(!entity.contains::<Label>());
+}
+
+#[test]
+fn extracts_fields() {
+    let mut app = app();
+    let entity = app.world.spawn(Speed(5.0)).id();
+
+    let fields = <TestPrefab as DerivedPrefab>::extract(app.world.entity(entity));
+    assert_eq!(fields.get("speed"), Some(&PrefabField::Number(5.0)));
+}
+
+#[test]
+fn schema_lists_fields() {
+    let schema = <TestPrefab as DerivedPrefab>::schema();
+
+    let sprite = schema.field("sprite").unwrap();
+    assert!(sprite.is_required() && sprite.is_asset());
+    assert!(schema.field("position").unwrap().is_required());
+    assert!(!schema.field("speed").unwrap().is_required());
+    assert!(!schema.field("name").unwrap().is_required());
+    assert!(schema.field("label").is_none());
+}
+
+#[test]
+fn registers_without_a_value() {
+    let mut registry = PrefabRegistry::default();
+    registry.register_derived::<TestPrefab>("Test");
+
+    assert!(registry.contains("Test"));
+    assert_eq!(registry.prefab_schema("Test").unwrap().fields().len(), 4);
+}
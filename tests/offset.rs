@@ -0,0 +1,258 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{ecs::system::EntityCommands, prelude::*, utils::HashMap};
+use hana_prefab::room::{
+    Prefab, PrefabChangeSet, PrefabFields, PrefabRegistry, PrefabSpawned, Room, RoomLoaderSettings,
+    RoomPlugin, RoomSpawned,
+};
+
+#[derive(Prefab)]
+struct Mover {
+    #[prefab(with = "position_transform")]
+    position: Vec2,
+}
+
+fn position_transform(position: Vec2) -> Transform {
+    Transform::from_translation(position.extend(0.0))
+}
+
+/// Moves its entity one unit further to the right every time it is updated
+struct Pusher;
+
+impl Prefab for Pusher {
+    fn spawn_prfab(
+        &self,
+        _fields: &PrefabFields,
+        mut commands: EntityCommands,
+        _asset_server: &AssetServer,
+    ) {
+        commands.insert(Transform::default());
+    }
+
+    fn update_prfab(
+        &self,
+        _changes: &PrefabChangeSet,
+        _asset_server: &AssetServer,
+        mut commands: EntityCommands,
+    ) {
+        commands.add(|mut entity: EntityWorldMut| {
+            if let Some(mut transform) = entity.get_mut::<Transform>() {
+                transform.translation.x += 1.0;
+            }
+        });
+    }
+}
+
+const ROOM: &str = r#"(
+    prefabs: {
+        "moved": (type: "Mover", fields: { "position": (1.0, 2.0) }),
+        "kept": (type: "Mover", fields: { "position": (0.0, 0.0) }),
+        "patched": (components: { "Transform": (translation: (x: 5.0, y: 6.0, z: 0.0)) }),
+    },
+)"#;
+
+const RELOADED: &str = r#"(
+    prefabs: {
+        "moved": (type: "Mover", fields: { "position": (3.0, 4.0) }),
+        "kept": (type: "Mover", fields: { "position": (0.0, 0.0) }),
+        "patched": (components: { "Transform": (translation: (x: 7.0, y: 6.0, z: 0.0)) }),
+    },
+)"#;
+
+fn assets_dir(test: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("hana_prefab_offset_{test}_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn app(assets: &Path) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin {
+            file_path: assets.to_string_lossy().into_owned(),
+            ..default()
+        },
+        TransformPlugin,
+        HierarchyPlugin,
+        RoomPlugin,
+    ));
+    let mut registry = app.world.resource_mut::<PrefabRegistry>();
+    registry.register_derived::<Mover>("Mover");
+    registry.register_prefab("Pusher", Pusher);
+    app
+}
+
+fn load_room(app: &mut App, path: &str, offset: f32) -> Handle<Room> {
+    app.world.resource::<AssetServer>().load_with_settings(
+        path.to_string(),
+        move |settings: &mut RoomLoaderSettings| {
+            settings.offset = Transform::from_xyz(offset, 0.0, 0.0);
+        },
+    )
+}
+
+/// Spawns `room.ron` with an offset of 100 and loads `reloaded.ron` with the given offset,
+/// returns both rooms and the spawned prefabs
+fn spawn_room(
+    app: &mut App,
+    assets: &Path,
+    reloaded_offset: f32,
+) -> (Handle<Room>, Handle<Room>, HashMap<String, Entity>) {
+    let room = load_room(app, "room.ron", 100.0);
+    let reloaded = load_room(app, "reloaded.ron", reloaded_offset);
+    app.world.spawn(room.clone());
+
+    let mut reader = app.world.resource::<Events<RoomSpawned>>().get_reader();
+    let mut prefab_reader = app.world.resource::<Events<PrefabSpawned>>().get_reader();
+    let mut entities = HashMap::new();
+    let mut spawned = false;
+    for _ in 0..500 {
+        app.update();
+        spawned |= reader
+            .read(app.world.resource::<Events<RoomSpawned>>())
+            .count()
+            > 0;
+        for event in prefab_reader.read(app.world.resource::<Events<PrefabSpawned>>()) {
+            entities.insert(event.key.clone(), event.entity);
+        }
+        let loaded = app.world.resource::<Assets<Room>>().contains(&reloaded);
+        if spawned && loaded {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(2));
+    }
+    std::fs::remove_dir_all(assets).unwrap();
+    app.update();
+    (room, reloaded, entities)
+}
+
+/// Swaps in the reloaded room, the way a hot reload replaces the room asset
+fn reload(app: &mut App, room: &Handle<Room>, reloaded: &Handle<Room>) {
+    let mut rooms = app.world.resource_mut::<Assets<Room>>();
+    let reloaded_room = rooms.remove(reloaded).unwrap();
+    rooms.insert(room, reloaded_room);
+    app.update();
+    app.update();
+}
+
+/// The translation of the entity in the world, composed from the transforms of its ancestors
+fn translation(app: &App, entity: Entity) -> Vec3 {
+    let mut transform = *app.world.get::<Transform>(entity).unwrap();
+    let mut current = entity;
+    while let Some(parent) = app.world.get::<Parent>(current) {
+        current = parent.get();
+        if let Some(parent_transform) = app.world.get::<Transform>(current) {
+            transform = *parent_transform * transform;
+        }
+    }
+    transform.translation
+}
+
+#[test]
+fn reloaded_transforms_keep_the_offset() {
+    let assets = assets_dir("reloaded");
+    std::fs::write(assets.join("room.ron"), ROOM).unwrap();
+    std::fs::write(assets.join("reloaded.ron"), RELOADED).unwrap();
+    let mut app = app(&assets);
+    let (room, reloaded, entities) = spawn_room(&mut app, &assets, 100.0);
+
+    assert_eq!(
+        translation(&app, entities["moved"]),
+        Vec3::new(101.0, 2.0, 0.0)
+    );
+    assert_eq!(
+        translation(&app, entities["patched"]),
+        Vec3::new(105.0, 6.0, 0.0)
+    );
+
+    reload(&mut app, &room, &reloaded);
+
+    assert_eq!(
+        translation(&app, entities["moved"]),
+        Vec3::new(103.0, 4.0, 0.0)
+    );
+    assert_eq!(
+        translation(&app, entities["kept"]),
+        Vec3::new(100.0, 0.0, 0.0)
+    );
+    assert_eq!(
+        translation(&app, entities["patched"]),
+        Vec3::new(107.0, 6.0, 0.0)
+    );
+}
+
+#[test]
+fn transform_written_with_its_old_value_keeps_the_offset() {
+    let assets = assets_dir("same_value");
+    std::fs::write(
+        assets.join("room.ron"),
+        r#"(prefabs: { "moved": (type: "Mover", fields: { "position": (1.0, 2.0) }) })"#,
+    )
+    .unwrap();
+    // The new position is the transform the prefab had with the offset applied
+    std::fs::write(
+        assets.join("reloaded.ron"),
+        r#"(prefabs: { "moved": (type: "Mover", fields: { "position": (101.0, 2.0) }) })"#,
+    )
+    .unwrap();
+    let mut app = app(&assets);
+    let (room, reloaded, entities) = spawn_room(&mut app, &assets, 100.0);
+
+    reload(&mut app, &room, &reloaded);
+
+    assert_eq!(
+        translation(&app, entities["moved"]),
+        Vec3::new(201.0, 2.0, 0.0)
+    );
+}
+
+#[test]
+fn relative_transform_change_keeps_the_offset() {
+    let assets = assets_dir("relative");
+    std::fs::write(
+        assets.join("room.ron"),
+        r#"(prefabs: { "pushed": (type: "Pusher", fields: { "pushes": 1 }) })"#,
+    )
+    .unwrap();
+    std::fs::write(
+        assets.join("reloaded.ron"),
+        r#"(prefabs: { "pushed": (type: "Pusher", fields: { "pushes": 2 }) })"#,
+    )
+    .unwrap();
+    let mut app = app(&assets);
+    let (room, reloaded, entities) = spawn_room(&mut app, &assets, 100.0);
+
+    assert_eq!(
+        translation(&app, entities["pushed"]),
+        Vec3::new(100.0, 0.0, 0.0)
+    );
+
+    reload(&mut app, &room, &reloaded);
+
+    assert_eq!(
+        translation(&app, entities["pushed"]),
+        Vec3::new(101.0, 0.0, 0.0)
+    );
+}
+
+#[test]
+fn reloaded_offset_moves_the_prefabs() {
+    let assets = assets_dir("moved_offset");
+    std::fs::write(assets.join("room.ron"), ROOM).unwrap();
+    std::fs::write(assets.join("reloaded.ron"), ROOM).unwrap();
+    let mut app = app(&assets);
+    let (room, reloaded, entities) = spawn_room(&mut app, &assets, 50.0);
+
+    reload(&mut app, &room, &reloaded);
+
+    assert_eq!(
+        translation(&app, entities["moved"]),
+        Vec3::new(51.0, 2.0, 0.0)
+    );
+    assert_eq!(
+        translation(&app, entities["patched"]),
+        Vec3::new(55.0, 6.0, 0.0)
+    );
+}
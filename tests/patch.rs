@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+use hana_prefab::room::{PrefabData, PrefabField, PrefabPatch, Room, RoomLoaderSettings};
+use indexmap::IndexMap;
+
+#[test]
+fn rooms_read_outside_of_bevy_can_be_diffed() {
+    let assets = std::env::temp_dir().join(format!("hana_prefab_patch_{}", std::process::id()));
+    std::fs::create_dir_all(assets.join("rooms")).unwrap();
+    std::fs::write(
+        assets.join("rooms/base.ron"),
+        r#"(prefabs: { "pig": (type: "Pig", fields: { "name": "pig" }) })"#,
+    )
+    .unwrap();
+    std::fs::write(
+        assets.join("rooms/room.ron"),
+        r#"(includes: ["rooms/base.ron"], prefabs: { "pen": (type: "Pen") })"#,
+    )
+    .unwrap();
+
+    let settings = RoomLoaderSettings {
+        key_prefix: "left_".to_string(),
+        ..default()
+    };
+    let room = Room::read(
+        &assets,
+        "rooms/room.ron",
+        &settings,
+        &AppTypeRegistry::default(),
+    );
+    std::fs::remove_dir_all(&assets).unwrap();
+    let room = room.unwrap();
+
+    assert_eq!(room.includes(), ["rooms/base.ron"]);
+    assert_eq!(room.settings().key_prefix, "left_");
+    let keys: Vec<&str> = room.prefabs().keys().map(String::as_str).collect();
+    assert_eq!(keys, ["left_pig", "left_pen"]);
+
+    let mut changed = room.clone();
+    changed
+        .prefabs_mut()
+        .get_mut("left_pig")
+        .unwrap()
+        .fields
+        .insert("name".to_string(), PrefabField::String("piggy".to_string()));
+    changed.prefabs_mut().shift_remove("left_pen");
+
+    let patch = Room::diff(&room, &changed);
+    assert!(matches!(
+        &patch.prefabs.changes[..],
+        [PrefabPatch::Changed { name, .. }, PrefabPatch::Removed { name: removed }]
+            if name == "left_pig" && removed == "left_pen"
+    ));
+
+    let mut patched = room.clone();
+    patch.apply(&mut patched).unwrap();
+    assert_eq!(patched.prefabs(), changed.prefabs());
+
+    let built = Room::new(
+        changed.prefabs().clone(),
+        IndexMap::<String, PrefabData>::new(),
+    );
+    assert!(Room::diff(&built, &changed).is_empty());
+}
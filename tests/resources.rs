@@ -0,0 +1,110 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use bevy::prelude::*;
+use hana_prefab::room::{
+    PrefabChangeSet, PrefabFields, PrefabRegistry, ResourcePrefab, Room, RoomPlugin, RoomSpawned,
+};
+
+#[derive(Resource)]
+struct Money(f32);
+
+/// Counts how often the resource is inserted, updated and removed
+#[derive(Default)]
+struct Calls {
+    inserted: AtomicUsize,
+    updated: AtomicUsize,
+    removed: AtomicUsize,
+}
+
+struct MoneyResource(Arc<Calls>);
+
+impl ResourcePrefab for MoneyResource {
+    fn insert_resource(
+        &self,
+        fields: &PrefabFields,
+        commands: &mut Commands,
+        _asset_server: &AssetServer,
+    ) {
+        self.0.inserted.fetch_add(1, Ordering::SeqCst);
+        commands.insert_resource(Money(fields.require("amount").unwrap()));
+    }
+
+    fn update_resource(
+        &self,
+        _changes: &PrefabChangeSet,
+        _commands: &mut Commands,
+        _asset_server: &AssetServer,
+    ) {
+        self.0.updated.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn remove_resource(&self, commands: &mut Commands) {
+        self.0.removed.fetch_add(1, Ordering::SeqCst);
+        commands.remove_resource::<Money>();
+    }
+}
+
+#[test]
+fn resources_are_shared_by_the_instances_of_a_room() {
+    let calls = Arc::new(Calls::default());
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        TransformPlugin,
+        HierarchyPlugin,
+        RoomPlugin,
+    ));
+    app.world
+        .resource_mut::<PrefabRegistry>()
+        .register_resource("Money", MoneyResource(calls.clone()));
+
+    let room: Handle<Room> = app
+        .world
+        .resource::<AssetServer>()
+        .load("rooms/test_room.ron");
+    let first = app.world.spawn(room.clone()).id();
+    let second = app.world.spawn(room.clone()).id();
+
+    let mut reader = app.world.resource::<Events<RoomSpawned>>().get_reader();
+    let mut spawned = 0;
+    for _ in 0..500 {
+        app.update();
+        spawned += reader
+            .read(app.world.resource::<Events<RoomSpawned>>())
+            .count();
+        if spawned == 2 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(2));
+    }
+    assert_eq!(spawned, 2, "both instances are spawned");
+    app.update();
+
+    assert_eq!(app.world.resource::<Money>().0, 100.0);
+    assert_eq!(calls.inserted.load(Ordering::SeqCst), 1);
+
+    // Despawning one instance keeps the resources of the other
+    app.world.entity_mut(first).despawn_recursive();
+    app.update();
+    app.update();
+    assert!(app.world.contains_resource::<Money>());
+    assert_eq!(calls.removed.load(Ordering::SeqCst), 0);
+
+    // Reloading a room whose resources did not change does not update them
+    app.world.resource_mut::<Assets<Room>>().get_mut(&room);
+    app.update();
+    app.update();
+    assert_eq!(calls.updated.load(Ordering::SeqCst), 0);
+    assert_eq!(calls.inserted.load(Ordering::SeqCst), 1);
+
+    // The resources are removed with the last instance
+    app.world.entity_mut(second).despawn_recursive();
+    app.update();
+    app.update();
+    assert!(!app.world.contains_resource::<Money>());
+    assert_eq!(calls.removed.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,173 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{ecs::system::SystemState, prelude::*, utils::HashMap};
+use hana_prefab::room::{
+    Prefab, PrefabField, PrefabRegistry, Room, RoomLoaderSettings, RoomPlugin, RoomSpawned,
+    RoomWriter,
+};
+
+#[derive(Component, Debug, PartialEq)]
+struct Speed(f32);
+
+impl From<f32> for Speed {
+    fn from(speed: f32) -> Self {
+        Self(speed)
+    }
+}
+
+#[derive(Component)]
+#[allow(dead_code)]
+struct Target(Entity);
+
+impl From<Entity> for Target {
+    fn from(entity: Entity) -> Self {
+        Self(entity)
+    }
+}
+
+#[derive(Prefab)]
+#[prefab(extract = "extract_mover")]
+struct Mover {
+    #[prefab(with = "position_transform")]
+    position: Vec2,
+    #[prefab(component = Speed, default = "default_speed")]
+    speed: f32,
+    #[prefab(component = Target)]
+    target: Option<Entity>,
+}
+
+fn position_transform(position: Vec2) -> Transform {
+    Transform::from_translation(position.extend(0.0))
+}
+
+fn default_speed() -> f32 {
+    5.0
+}
+
+fn extract_mover(entity: EntityRef) -> HashMap<String, PrefabField> {
+    entity
+        .get::<Transform>()
+        .map(|transform| {
+            let position = transform.translation;
+            (
+                "position".to_string(),
+                PrefabField::Vec2(position.x, position.y),
+            )
+        })
+        .into_iter()
+        .collect()
+}
+
+const ROOM: &str = r#"(
+    prefabs: {
+        "a": (type: "Mover", fields: { "position": (1.0, 2.0), "target": Ref(prefab: "b") }),
+        "b": (type: "Mover", fields: { "position": (3.0, 4.0), "speed": 7.0 }, after: ["a"]),
+        "c": (components: { "Transform": (translation: (x: 5.0, y: 6.0, z: 0.0)) }),
+    },
+)"#;
+
+fn assets_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hana_prefab_writer_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn app(assets: &Path) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin {
+            file_path: assets.to_string_lossy().into_owned(),
+            ..default()
+        },
+        TransformPlugin,
+        HierarchyPlugin,
+        RoomPlugin,
+    ));
+    app.world
+        .resource_mut::<PrefabRegistry>()
+        .register_derived::<Mover>("Mover");
+    app
+}
+
+/// Loads the room with an offset and a key prefix and waits until it is spawned,
+/// returns the instance and the spawned prefabs
+fn spawn_room(app: &mut App, path: &str) -> (Entity, Vec<Entity>) {
+    let room: Handle<Room> = app.world.resource::<AssetServer>().load_with_settings(
+        path.to_string(),
+        |settings: &mut RoomLoaderSettings| {
+            settings.offset = Transform::from_xyz(100.0, 0.0, 0.0);
+            settings.key_prefix = "left_".to_string();
+        },
+    );
+    let instance = app.world.spawn(room).id();
+
+    let mut reader = app.world.resource::<Events<RoomSpawned>>().get_reader();
+    for _ in 0..500 {
+        app.update();
+        let events = app.world.resource::<Events<RoomSpawned>>();
+        if let Some(event) = reader.read(events).find(|event| event.instance == instance) {
+            let entities = event.entities.values().copied().collect();
+            app.update();
+            return (instance, entities);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(2));
+    }
+    panic!("room {path} was not spawned");
+}
+
+/// The translation of the entity in the world, composed from the transforms of its ancestors
+fn translation(app: &App, entity: Entity) -> Vec3 {
+    let mut transform = *app.world.get::<Transform>(entity).unwrap();
+    let mut current = entity;
+    while let Some(parent) = app.world.get::<Parent>(current) {
+        current = parent.get();
+        if let Some(parent_transform) = app.world.get::<Transform>(current) {
+            transform = *parent_transform * transform;
+        }
+    }
+    transform.translation
+}
+
+fn room_to_ron(app: &mut App, instance: Entity) -> String {
+    let mut state = SystemState::<RoomWriter>::new(&mut app.world);
+    state.get(&app.world).room_to_ron(instance).unwrap()
+}
+
+#[test]
+fn saved_room_loads_the_same_way() {
+    let assets = assets_dir();
+    std::fs::write(assets.join("room.ron"), ROOM).unwrap();
+    let mut app = app(&assets);
+
+    let (instance, entities) = spawn_room(&mut app, "room.ron");
+    let ron = room_to_ron(&mut app, instance);
+
+    assert!(!ron.contains("left_"), "key prefix was written:\n{ron}");
+    assert!(!ron.contains("100"), "offset was written:\n{ron}");
+    assert_eq!(
+        ron.matches("speed").count(),
+        1,
+        "default was written:\n{ron}"
+    );
+
+    std::fs::write(assets.join("saved.ron"), &ron).unwrap();
+    let (saved_instance, saved_entities) = spawn_room(&mut app, "saved.ron");
+    let saved_ron = room_to_ron(&mut app, saved_instance);
+    std::fs::remove_dir_all(&assets).unwrap();
+    assert_eq!(ron, saved_ron);
+
+    // The prefabs of both instances are spawned at the same place
+    let translations = |entities: &[Entity]| {
+        let mut translations: Vec<[f32; 3]> = entities
+            .iter()
+            .map(|entity| translation(&app, *entity).to_array())
+            .collect();
+        translations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        translations
+    };
+    let spawned = translations(&entities);
+    assert_eq!(spawned.len(), 3);
+    assert_eq!(spawned[0], [101.0, 2.0, 0.0]);
+    assert_eq!(spawned, translations(&saved_entities));
+}